@@ -0,0 +1,317 @@
+//! Periodic bandwidth accounting for low, full and high-speed buses.
+//!
+//! Bus times follow the formulas of the USB 2.0 specification, section 5.11.3, including worst
+//! case bit stuffing.
+
+use crate::{
+    descriptor::{Direction, Endpoint, EndpointAddress, EndpointTransfer},
+    InvalidServiceInterval, ServiceInterval, Speed,
+};
+
+/// Host controller delay in nanoseconds.
+const HOST_DELAY: u32 = 1000;
+/// Low-speed setup time of a hub in nanoseconds.
+const HUB_LS_SETUP: u32 = 333;
+/// High-speed host controller delay in nanoseconds.
+const HS_HOST_DELAY: u32 = 5;
+
+/// Bit times of a packet with `bytes` of data, `Floor(3.167 + BitStuffTime(bytes))`.
+const fn bit_time(bytes: u32) -> u32 {
+    (3167 + 7 * 8 * 1000 * bytes / 6) / 1000
+}
+
+/// The worst case time in nanoseconds a transaction with `bytes` of data occupies the bus.
+///
+/// Returns `None` for SuperSpeed, which has no such formula, and for more data than a packet at
+/// `speed` can carry.
+pub const fn bus_time(speed: Speed, direction: Direction, isoch: bool, bytes: u32) -> Option<u32> {
+    let input = matches!(direction, Direction::In);
+    let max = match speed {
+        Speed::Low => 8,
+        Speed::Full => 1023,
+        Speed::High => 1024,
+        Speed::Super | Speed::SuperPlus => return None,
+    };
+    if bytes > max {
+        return None;
+    }
+    let bits = bit_time(bytes);
+    Some(match speed {
+        Speed::Low => {
+            let (overhead, bit) = match input {
+                true => (64060, 67667),
+                false => (64107, 66700),
+            };
+            overhead + 2 * HUB_LS_SETUP + HOST_DELAY + bit * bits / 100
+        }
+        Speed::Full => {
+            let overhead = match (isoch, input) {
+                (true, true) => 7268,
+                (true, false) => 6265,
+                (false, _) => 9107,
+            };
+            overhead + HOST_DELAY + 8354 * bits / 100
+        }
+        _ => {
+            let overhead = if isoch { 38 } else { 55 };
+            (overhead * 8 * 2083 + 2083 * bits) / 1000 + HS_HOST_DELAY
+        }
+    })
+}
+
+/// The bus time reserved for one periodic endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reservation {
+    pub address: EndpointAddress,
+    /// The first slot the endpoint is serviced in.
+    pub phase: u8,
+    /// The endpoint is serviced every `period` slots.
+    pub period: u8,
+    /// Nanoseconds used in every slot the endpoint is serviced in.
+    pub nanos: u32,
+}
+
+#[derive(Debug)]
+pub enum BandwidthError {
+    Endpoint(InvalidServiceInterval),
+    /// No phase leaves enough time in every slot.
+    Exceeded(EndpointAddress),
+}
+
+/// The periodic load of a bus, in nanoseconds per slot.
+///
+/// A slot is a frame at low and full speed and a microframe at high speed. The schedule repeats
+/// every [`Schedule::SLOTS`] slots, so longer periods are shortened to that.
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    speed: Speed,
+    load: [u32; Self::SLOTS],
+}
+
+impl Schedule {
+    pub const SLOTS: usize = 32;
+
+    /// An empty schedule, or `None` for SuperSpeed buses.
+    pub const fn new(speed: Speed) -> Option<Self> {
+        match speed {
+            Speed::Super | Speed::SuperPlus => None,
+            _ => Some(Self {
+                speed,
+                load: [0; Self::SLOTS],
+            }),
+        }
+    }
+
+    pub const fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// The nanoseconds reserved in each slot.
+    pub const fn load(&self) -> &[u32; Self::SLOTS] {
+        &self.load
+    }
+
+    /// The nanoseconds that may be reserved in a slot.
+    ///
+    /// This is 90% of a frame or 80% of a microframe.
+    pub const fn budget(&self) -> u32 {
+        match self.speed {
+            Speed::High => 100_000,
+            _ => 900_000,
+        }
+    }
+
+    /// Reserve time for a periodic endpoint in the phase with the lowest resulting load.
+    ///
+    /// Returns `None` for control and bulk endpoints.
+    pub fn reserve(&mut self, endpoint: &Endpoint) -> Result<Option<Reservation>, BandwidthError> {
+        let microframes = match self.speed.service_interval(endpoint) {
+            Ok(ServiceInterval::Periodic { microframes }) => microframes,
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(BandwidthError::Endpoint(e)),
+        };
+        let slots = match self.speed {
+            Speed::High => microframes,
+            _ => microframes / 8,
+        };
+        let period = 1 << slots.min(Self::SLOTS as u32).ilog2();
+        let isoch = matches!(endpoint.attributes.transfer(), EndpointTransfer::Isoch);
+        let mps = endpoint.max_packet_size;
+        let direction = endpoint.address.direction();
+        // The schedule is never SuperSpeed and the max packet size is valid for its speed.
+        let transaction = bus_time(self.speed, direction, isoch, mps.size().into()).unwrap();
+        // Each transaction of a high-bandwidth endpoint has its own overhead.
+        let nanos = (1 + u32::from(mps.additional_transactions())) * transaction;
+        let peak = |phase: usize| {
+            let slots = self.load.iter().skip(phase).step_by(period);
+            slots.map(|l| l + nanos).max().unwrap()
+        };
+        let phase = (0..period).min_by_key(|&p| peak(p)).unwrap();
+        if peak(phase) > self.budget() {
+            return Err(BandwidthError::Exceeded(endpoint.address));
+        }
+        self.load
+            .iter_mut()
+            .skip(phase)
+            .step_by(period)
+            .for_each(|l| *l += nanos);
+        Ok(Some(Reservation {
+            address: endpoint.address,
+            phase: phase as u8,
+            period: period as u8,
+            nanos,
+        }))
+    }
+
+    /// Reserve time for all `endpoints` of an alternate setting.
+    ///
+    /// Every reservation is reported to `f`. On error nothing is reserved and `f` is not called.
+    pub fn reserve_all<'e, I>(
+        &mut self,
+        endpoints: I,
+        mut f: impl FnMut(Reservation),
+    ) -> Result<(), BandwidthError>
+    where
+        I: IntoIterator<Item = &'e Endpoint>,
+        I::IntoIter: Clone,
+    {
+        let endpoints = endpoints.into_iter();
+        let mut s = *self;
+        for e in endpoints.clone() {
+            s.reserve(e)?;
+        }
+        for e in endpoints {
+            if let Some(r) = self.reserve(e)? {
+                f(r);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the time of a reservation.
+    ///
+    /// Only reservations made by this schedule should be released. The load of a slot does not
+    /// drop below 0.
+    pub fn release(&mut self, reservation: &Reservation) {
+        let slots = self.load.iter_mut().skip(reservation.phase.into());
+        slots
+            .step_by(reservation.period.into())
+            .for_each(|l| *l = l.saturating_sub(reservation.nanos));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::descriptor::{EndpointNumber, MaxPacketSize};
+
+    #[test]
+    fn bus_time() {
+        assert_eq!(
+            super::bus_time(Speed::Full, Direction::In, true, 1023),
+            Some(806_158)
+        );
+        assert_eq!(
+            super::bus_time(Speed::High, Direction::In, false, 512),
+            Some(10_880)
+        );
+        assert_eq!(
+            super::bus_time(Speed::Low, Direction::Out, false, 8),
+            Some(117_132)
+        );
+        assert_eq!(
+            super::bus_time(Speed::Super, Direction::In, false, 1024),
+            None
+        );
+        assert_eq!(super::bus_time(Speed::Low, Direction::In, false, 9), None);
+        assert_eq!(
+            super::bus_time(Speed::Full, Direction::In, false, u32::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn schedule() {
+        use EndpointNumber::*;
+        assert!(Schedule::new(Speed::Super).is_none());
+        let mut s = Schedule::new(Speed::High).unwrap();
+        let mut r = [None; 4];
+        let mut n = 0;
+        let setting = [
+            Endpoint::new(
+                EndpointAddress::new(N1, Direction::In),
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(64, 0),
+                4,
+            ),
+            Endpoint::new(
+                EndpointAddress::new(N2, Direction::In),
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(64, 0),
+                4,
+            ),
+            Endpoint::new(
+                EndpointAddress::new(N3, Direction::In),
+                EndpointTransfer::Bulk,
+                MaxPacketSize::new(512, 0),
+                0,
+            ),
+            Endpoint::new(
+                EndpointAddress::new(N4, Direction::In),
+                EndpointTransfer::Isoch,
+                MaxPacketSize::new(1024, 0),
+                1,
+            ),
+        ];
+        s.reserve_all(&setting, |x| {
+            r[n] = Some(x);
+            n += 1;
+        })
+        .unwrap();
+        assert_eq!(n, 3);
+        let r = [r[0].unwrap(), r[1].unwrap(), r[2].unwrap()];
+        assert_eq!((r[0].phase, r[0].period), (0, 8));
+        assert_eq!((r[1].phase, r[1].period), (1, 8));
+        assert_eq!((r[2].phase, r[2].period), (0, 1));
+        assert_eq!(s.load()[0], r[0].nanos + r[2].nanos);
+        assert_eq!(s.load()[2], r[2].nanos);
+
+        // Five isochronous endpoints don't fit into 80% of a microframe.
+        let before = *s.load();
+        let more = [N5, N6, N7, N8].map(|n| {
+            Endpoint::new(
+                EndpointAddress::new(n, Direction::In),
+                EndpointTransfer::Isoch,
+                MaxPacketSize::new(1024, 0),
+                1,
+            )
+        });
+        assert!(matches!(
+            s.reserve_all(&more, |_| panic!()),
+            Err(BandwidthError::Exceeded(a)) if a == more[3].address
+        ));
+        assert_eq!(*s.load(), before);
+        s.release(&r[2]);
+        s.release(&r[1]);
+        s.release(&r[0]);
+        assert_eq!(*s.load(), [0; Schedule::SLOTS]);
+        s.release(&r[0]);
+        assert_eq!(*s.load(), [0; Schedule::SLOTS]);
+    }
+
+    #[test]
+    fn high_bandwidth() {
+        let mut s = Schedule::new(Speed::High).unwrap();
+        let mut e = Endpoint::new(
+            EndpointAddress::new(EndpointNumber::N1, Direction::In),
+            EndpointTransfer::Isoch,
+            MaxPacketSize::new(1024, 0),
+            1,
+        );
+        e.max_packet_size = MaxPacketSize::new(1024, 2);
+        let r = s.reserve(&e).unwrap().unwrap();
+        // Three transactions of 1024 bytes, not one of 3072.
+        assert_eq!(r.nanos, 3 * 20_551);
+        assert_eq!(s.load()[0], r.nanos);
+    }
+}
@@ -0,0 +1,235 @@
+//! Class, subclass and protocol codes.
+
+use core::fmt;
+
+/// A class/subclass/protocol triple as found in device, interface and interface association
+/// descriptors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClassCode {
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+}
+
+impl ClassCode {
+    pub const fn new(class: u8, subclass: u8, protocol: u8) -> Self {
+        Self {
+            class,
+            subclass,
+            protocol,
+        }
+    }
+
+    pub fn base(&self) -> BaseClass {
+        self.class.into()
+    }
+
+    /// The well-known function this triple identifies, if any.
+    pub fn well_known(&self) -> Option<WellKnown> {
+        WellKnown::from_code(*self)
+    }
+}
+
+impl fmt::Display for ClassCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}/{:02x}/{:02x} ",
+            self.class, self.subclass, self.protocol
+        )?;
+        match self.well_known() {
+            Some(w) => write!(f, "({})", w.name()),
+            None => write!(f, "({})", self.base()),
+        }
+    }
+}
+
+macro_rules! base_classes {
+    ($($v:ident $c:literal $n:literal)*) => {
+        /// A USB-IF defined base class.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum BaseClass {
+            $($v,)*
+            Unknown(u8),
+        }
+
+        impl BaseClass {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$v => $n,)*
+                    Self::Unknown(_) => "Unknown",
+                }
+            }
+        }
+
+        impl From<u8> for BaseClass {
+            fn from(c: u8) -> Self {
+                match c {
+                    $($c => Self::$v,)*
+                    c => Self::Unknown(c),
+                }
+            }
+        }
+
+        impl From<BaseClass> for u8 {
+            fn from(c: BaseClass) -> Self {
+                match c {
+                    $(BaseClass::$v => $c,)*
+                    BaseClass::Unknown(c) => c,
+                }
+            }
+        }
+    };
+}
+
+base_classes! {
+    PerInterface 0x00 "Defined per interface"
+    Audio 0x01 "Audio"
+    Communications 0x02 "Communications and CDC Control"
+    Hid 0x03 "Human Interface Device"
+    Physical 0x05 "Physical"
+    Image 0x06 "Image"
+    Printer 0x07 "Printer"
+    MassStorage 0x08 "Mass Storage"
+    Hub 0x09 "Hub"
+    CdcData 0x0a "CDC Data"
+    SmartCard 0x0b "Smart Card"
+    ContentSecurity 0x0d "Content Security"
+    Video 0x0e "Video"
+    PersonalHealthcare 0x0f "Personal Healthcare"
+    AudioVideo 0x10 "Audio/Video"
+    Billboard 0x11 "Billboard"
+    TypeCBridge 0x12 "USB Type-C Bridge"
+    BulkDisplay 0x13 "Bulk Display"
+    Mctp 0x14 "MCTP over USB"
+    I3c 0x3c "I3C"
+    Diagnostic 0xdc "Diagnostic Device"
+    WirelessController 0xe0 "Wireless Controller"
+    Miscellaneous 0xef "Miscellaneous"
+    ApplicationSpecific 0xfe "Application Specific"
+    VendorSpecific 0xff "Vendor Specific"
+}
+
+impl fmt::Display for BaseClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+macro_rules! well_known {
+    ($($v:ident $c:literal $s:literal $p:literal $n:literal)*) => {
+        /// A well-known class/subclass/protocol triple.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum WellKnown {
+            $($v,)*
+        }
+
+        impl WellKnown {
+            pub fn from_code(c: ClassCode) -> Option<Self> {
+                Some(match (c.class, c.subclass, c.protocol) {
+                    $(($c, $s, $p) => Self::$v,)*
+                    _ => return None,
+                })
+            }
+
+            pub const fn code(&self) -> ClassCode {
+                match self {
+                    $(Self::$v => ClassCode::new($c, $s, $p),)*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$v => $n,)*
+                }
+            }
+        }
+    };
+}
+
+well_known! {
+    AudioControl 0x01 0x01 0x00 "Audio Control"
+    AudioStreaming 0x01 0x02 0x00 "Audio Streaming"
+    MidiStreaming 0x01 0x03 0x00 "MIDI Streaming"
+    AudioControl2 0x01 0x01 0x20 "Audio Control (2.0)"
+    AudioStreaming2 0x01 0x02 0x20 "Audio Streaming (2.0)"
+    CdcAcm 0x02 0x02 0x01 "CDC ACM (AT commands)"
+    CdcAcmNoProtocol 0x02 0x02 0x00 "CDC ACM"
+    CdcEcm 0x02 0x06 0x00 "CDC Ethernet"
+    CdcNcm 0x02 0x0d 0x00 "CDC NCM"
+    CdcMbim 0x02 0x0e 0x00 "CDC MBIM"
+    HidNone 0x03 0x00 0x00 "HID"
+    HidBootKeyboard 0x03 0x01 0x01 "HID Boot Keyboard"
+    HidBootMouse 0x03 0x01 0x02 "HID Boot Mouse"
+    StillImage 0x06 0x01 0x01 "Still Image Capture"
+    PrinterUnidirectional 0x07 0x01 0x01 "Printer (unidirectional)"
+    PrinterBidirectional 0x07 0x01 0x02 "Printer (bidirectional)"
+    PrinterIeee1284_4 0x07 0x01 0x03 "Printer (IEEE 1284.4)"
+    MassStorageUfi 0x08 0x04 0x00 "Mass Storage UFI (CBI)"
+    MassStorageBulkOnly 0x08 0x06 0x50 "Mass Storage SCSI (Bulk-Only)"
+    MassStorageUas 0x08 0x06 0x62 "Mass Storage SCSI (UAS)"
+    HubFullSpeed 0x09 0x00 0x00 "Full Speed Hub"
+    HubSingleTt 0x09 0x00 0x01 "High Speed Hub (single TT)"
+    HubMultiTt 0x09 0x00 0x02 "High Speed Hub (multiple TTs)"
+    HubSuperSpeed 0x09 0x00 0x03 "SuperSpeed Hub"
+    CdcData 0x0a 0x00 0x00 "CDC Data"
+    SmartCard 0x0b 0x00 0x00 "Smart Card (CCID)"
+    VideoControl 0x0e 0x01 0x00 "Video Control"
+    VideoStreaming 0x0e 0x02 0x00 "Video Streaming"
+    Billboard 0x11 0x00 0x00 "Billboard"
+    Bluetooth 0xe0 0x01 0x01 "Bluetooth"
+    UwbRadioControl 0xe0 0x01 0x02 "UWB Radio Control"
+    Rndis 0xe0 0x01 0x03 "RNDIS"
+    InterfaceAssociation 0xef 0x02 0x01 "Interface Association"
+    MiscRndisEthernet 0xef 0x04 0x01 "RNDIS over Ethernet"
+    Dfu 0xfe 0x01 0x01 "DFU (runtime)"
+    DfuMode 0xfe 0x01 0x02 "DFU (DFU mode)"
+    IrdaBridge 0xfe 0x02 0x00 "IrDA Bridge"
+    UsbTmc 0xfe 0x03 0x00 "Test and Measurement"
+    UsbTmc488 0xfe 0x03 0x01 "Test and Measurement (USB488)"
+}
+
+impl From<WellKnown> for ClassCode {
+    fn from(w: WellKnown) -> Self {
+        w.code()
+    }
+}
+
+impl fmt::Display for WellKnown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn class_code() {
+        let c = ClassCode::new(3, 1, 1);
+        assert_eq!(c.base(), BaseClass::Hid);
+        assert_eq!(c.well_known(), Some(WellKnown::HidBootKeyboard));
+        assert_eq!(WellKnown::HidBootKeyboard.code(), c);
+        let c = ClassCode::new(0xff, 0x42, 0x01);
+        assert_eq!(c.base(), BaseClass::VendorSpecific);
+        assert_eq!(c.well_known(), None);
+        assert_eq!(BaseClass::from(0x77), BaseClass::Unknown(0x77));
+        for c in [0, 1, 0x0a, 0x3c, 0xef, 0xff, 0x77] {
+            assert_eq!(u8::from(BaseClass::from(c)), c);
+        }
+    }
+
+    #[test]
+    fn interface_class_code() {
+        use crate::descriptor::{decode, Descriptor};
+        let interface = [0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00];
+        let Some(Ok(Descriptor::Interface(i))) = decode(&interface).next() else {
+            panic!("not an interface");
+        };
+        assert_eq!(
+            i.class_code().well_known(),
+            Some(WellKnown::HidBootKeyboard)
+        );
+    }
+}
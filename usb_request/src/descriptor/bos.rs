@@ -0,0 +1,415 @@
+use core::fmt;
+
+/// Binary device Object Store header.
+///
+/// This is followed by `num_device_caps` device capability descriptors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bos {
+    pub total_length: u16,
+    pub num_device_caps: u8,
+}
+
+impl Bos {
+    pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidBos> {
+        if let &[a, b, c, ..] = buf {
+            Ok(Bos {
+                total_length: u16::from_le_bytes([a, b]),
+                num_device_caps: c,
+            })
+        } else {
+            Err(InvalidBos::UnexpectedLength)
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 5] {
+        let [l0, l1] = self.total_length.to_le_bytes();
+        [5, super::BOS, l0, l1, self.num_device_caps]
+    }
+
+    write_into!();
+}
+
+#[derive(Debug)]
+pub enum InvalidBos {
+    UnexpectedLength,
+}
+
+#[derive(Debug)]
+pub enum DeviceCapability<'a> {
+    Usb2Extension(Usb2Extension),
+    SuperSpeed(SuperSpeed),
+    ContainerId(ContainerId),
+    Platform(Platform<'a>),
+    SuperSpeedPlus(SuperSpeedPlus<'a>),
+    Unknown { ty: u8, data: &'a [u8] },
+}
+
+impl<'a> DeviceCapability<'a> {
+    const USB2_EXTENSION: u8 = 0x2;
+    const SUPERSPEED: u8 = 0x3;
+    const CONTAINER_ID: u8 = 0x4;
+    const PLATFORM: u8 = 0x5;
+    const SUPERSPEED_PLUS: u8 = 0xa;
+
+    pub(crate) fn from_raw(buf: &'a [u8]) -> Result<Self, InvalidDeviceCapability> {
+        use InvalidDeviceCapability::UnexpectedLength;
+        let (&ty, b) = buf.split_first().ok_or(UnexpectedLength)?;
+        let uuid = |b: &[u8]| b.try_into().map(Uuid).map_err(|_| UnexpectedLength);
+        Ok(match ty {
+            Self::USB2_EXTENSION => match *b {
+                [a, b, c, d] => {
+                    Self::Usb2Extension(Usb2Extension(u32::from_le_bytes([a, b, c, d])))
+                }
+                _ => return Err(UnexpectedLength),
+            },
+            Self::SUPERSPEED => match *b {
+                [a, b, c, d, e, f, g] => Self::SuperSpeed(SuperSpeed {
+                    attributes: a,
+                    speeds_supported: u16::from_le_bytes([b, c]),
+                    functionality_support: d,
+                    u1_exit_latency: e,
+                    u2_exit_latency: u16::from_le_bytes([f, g]),
+                }),
+                _ => return Err(UnexpectedLength),
+            },
+            Self::CONTAINER_ID => match b {
+                [_, id @ ..] => Self::ContainerId(ContainerId { id: uuid(id)? }),
+                _ => return Err(UnexpectedLength),
+            },
+            Self::PLATFORM => match b {
+                [_, rest @ ..] if rest.len() >= 16 => {
+                    let (id, data) = rest.split_at(16);
+                    Self::Platform(Platform {
+                        id: uuid(id)?,
+                        data,
+                    })
+                }
+                _ => return Err(UnexpectedLength),
+            },
+            Self::SUPERSPEED_PLUS => match b {
+                [_, a0, a1, a2, a3, f0, f1, _, _, rest @ ..] => {
+                    let attributes = u32::from_le_bytes([*a0, *a1, *a2, *a3]);
+                    let (sublink_speeds, rem) = rest.as_chunks();
+                    let count = (attributes & 0x1f) as usize + 1;
+                    if !rem.is_empty() || sublink_speeds.len() != count {
+                        return Err(UnexpectedLength);
+                    }
+                    Self::SuperSpeedPlus(SuperSpeedPlus {
+                        attributes,
+                        functionality_support: u16::from_le_bytes([*f0, *f1]),
+                        sublink_speeds,
+                    })
+                }
+                _ => return Err(UnexpectedLength),
+            },
+            ty => Self::Unknown { ty, data: b },
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidDeviceCapability {
+    UnexpectedLength,
+}
+
+/// USB 2.0 Extension capability, which describes Link Power Management support.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Usb2Extension(u32);
+
+impl Usb2Extension {
+    /// Whether Link Power Management is supported.
+    pub fn lpm(&self) -> bool {
+        self.0 & 1 << 1 != 0
+    }
+
+    /// Whether BESL and alternate HIRD definitions are supported.
+    pub fn besl(&self) -> bool {
+        self.0 & 1 << 2 != 0
+    }
+
+    /// The recommended baseline BESL value, if any.
+    pub fn baseline_besl(&self) -> Option<u8> {
+        (self.0 & 1 << 3 != 0).then_some((self.0 >> 8 & 0xf) as u8)
+    }
+
+    /// The recommended deep BESL value, if any.
+    pub fn deep_besl(&self) -> Option<u8> {
+        (self.0 & 1 << 4 != 0).then_some((self.0 >> 12 & 0xf) as u8)
+    }
+}
+
+impl fmt::Debug for Usb2Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(stringify!(Usb2Extension))
+            .field("lpm", &self.lpm())
+            .field("besl", &self.besl())
+            .field("baseline_besl", &self.baseline_besl())
+            .field("deep_besl", &self.deep_besl())
+            .finish()
+    }
+}
+
+/// SuperSpeed USB capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuperSpeed {
+    pub attributes: u8,
+    pub speeds_supported: u16,
+    /// The lowest speed at which all functionality is available.
+    pub functionality_support: u8,
+    /// U1 exit latency in microseconds.
+    pub u1_exit_latency: u8,
+    /// U2 exit latency in microseconds.
+    pub u2_exit_latency: u16,
+}
+
+impl SuperSpeed {
+    /// Whether the device can generate Latency Tolerance Messages.
+    pub fn ltm(&self) -> bool {
+        self.attributes & 1 << 1 != 0
+    }
+
+    pub fn low_speed(&self) -> bool {
+        self.speeds_supported & 1 << 0 != 0
+    }
+
+    pub fn full_speed(&self) -> bool {
+        self.speeds_supported & 1 << 1 != 0
+    }
+
+    pub fn high_speed(&self) -> bool {
+        self.speeds_supported & 1 << 2 != 0
+    }
+
+    /// Whether 5 Gb/s operation is supported.
+    pub fn gen1(&self) -> bool {
+        self.speeds_supported & 1 << 3 != 0
+    }
+}
+
+/// A unique identifier of the device instance, which is the same for all ports it is
+/// connected to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContainerId {
+    pub id: Uuid,
+}
+
+/// A platform or OS specific capability.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Platform<'a> {
+    pub id: Uuid,
+    pub data: &'a [u8],
+}
+
+/// SuperSpeedPlus USB capability.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SuperSpeedPlus<'a> {
+    pub attributes: u32,
+    pub functionality_support: u16,
+    sublink_speeds: &'a [[u8; 4]],
+}
+
+impl<'a> SuperSpeedPlus<'a> {
+    /// The amount of sublink speed IDs, i.e. distinct speeds.
+    pub fn sublink_speed_id_count(&self) -> u8 {
+        (self.attributes >> 5 & 0xf) as u8 + 1
+    }
+
+    /// The sublink speed ID of the lowest speed at which all functionality is available.
+    pub fn min_sublink_speed_id(&self) -> u8 {
+        (self.functionality_support & 0xf) as u8
+    }
+
+    /// The minimum amount of receive lanes.
+    pub fn min_rx_lanes(&self) -> u8 {
+        (self.functionality_support >> 8 & 0xf) as u8
+    }
+
+    /// The minimum amount of transmit lanes.
+    pub fn min_tx_lanes(&self) -> u8 {
+        (self.functionality_support >> 12 & 0xf) as u8
+    }
+
+    pub fn sublink_speeds(&self) -> impl ExactSizeIterator<Item = SublinkSpeed> + 'a {
+        self.sublink_speeds
+            .iter()
+            .map(|b| SublinkSpeed(u32::from_le_bytes(*b)))
+    }
+}
+
+impl fmt::Debug for SuperSpeedPlus<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Speeds<'a, 'b>(&'b SuperSpeedPlus<'a>);
+        impl fmt::Debug for Speeds<'_, '_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_list().entries(self.0.sublink_speeds()).finish()
+            }
+        }
+        f.debug_struct(stringify!(SuperSpeedPlus))
+            .field("sublink_speed_id_count", &self.sublink_speed_id_count())
+            .field("min_sublink_speed_id", &self.min_sublink_speed_id())
+            .field("min_rx_lanes", &self.min_rx_lanes())
+            .field("min_tx_lanes", &self.min_tx_lanes())
+            .field("sublink_speeds", &Speeds(self))
+            .finish()
+    }
+}
+
+/// Attributes of a single sublink speed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SublinkSpeed(u32);
+
+impl SublinkSpeed {
+    /// The ID used to refer to this speed.
+    pub fn id(&self) -> u8 {
+        (self.0 & 0xf) as u8
+    }
+
+    /// Whether the receive and transmit speeds differ.
+    pub fn asymmetric(&self) -> bool {
+        self.0 & 1 << 6 != 0
+    }
+
+    /// Whether this is the transmit speed.
+    ///
+    /// Only meaningful for asymmetric sublinks.
+    pub fn transmit(&self) -> bool {
+        self.0 & 1 << 7 != 0
+    }
+
+    pub fn protocol(&self) -> LinkProtocol {
+        match self.0 >> 14 & 0x3 {
+            0 => LinkProtocol::SuperSpeed,
+            1 => LinkProtocol::SuperSpeedPlus,
+            n => LinkProtocol::Reserved(n as u8),
+        }
+    }
+
+    /// The lane speed in bits per second.
+    pub fn bits_per_second(&self) -> u64 {
+        let mantissa = u64::from(self.0 >> 16);
+        mantissa * [1, 1_000, 1_000_000, 1_000_000_000][(self.0 >> 4 & 0x3) as usize]
+    }
+}
+
+impl fmt::Debug for SublinkSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(stringify!(SublinkSpeed))
+            .field("id", &self.id())
+            .field("asymmetric", &self.asymmetric())
+            .field("transmit", &self.transmit())
+            .field("protocol", &self.protocol())
+            .field("bits_per_second", &self.bits_per_second())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkProtocol {
+    SuperSpeed,
+    SuperSpeedPlus,
+    Reserved(u8),
+}
+
+/// A 128-bit UUID.
+///
+/// The first three fields are stored in little-endian order, as specified by
+/// RFC 4122 for binary encodings on little-endian systems.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uuid(pub [u8; 16]);
+
+impl fmt::Debug for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9],
+        )?;
+        b[10..].iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{decode, Descriptor};
+    use super::*;
+
+    const BOS: &[u8] = &[
+        0x05, 0x0f, 0x5a, 0x00, 0x05, // bos
+        0x07, 0x10, 0x02, 0x1e, 0x64, 0x00, 0x00, // usb 2.0 extension
+        0x0a, 0x10, 0x03, 0x00, 0x0e, 0x00, 0x01, 0x0a, 0xff, 0x07, // superspeed
+        0x14, 0x10, 0x04, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa,
+        0xbb, 0xcc, 0xdd, 0xee, 0xff, // container id
+        0x1c, 0x10, 0x05, 0x00, 0xdf, 0x60, 0xdd, 0xd8, 0x89, 0x45, 0xc7, 0x4c, 0x9c, 0xd2, 0x65,
+        0x9d, 0x9e, 0x64, 0x8a, 0x9f, 0x00, 0x00, 0x03, 0x06, 0xb2, 0x00, 0x01,
+        0x00, // platform
+        0x14, 0x10, 0x0a, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x30, 0x40, 0x0a,
+        0x00, 0xb0, 0x40, 0x0a, 0x00, // superspeedplus
+    ];
+
+    #[test]
+    fn bos() {
+        let mut it = decode(BOS);
+        let cap = |d| match d {
+            Some(Ok(Descriptor::DeviceCapability(c))) => c,
+            d => panic!("{:?}", d),
+        };
+        match it.next() {
+            Some(Ok(Descriptor::Bos(b))) => {
+                assert_eq!(usize::from(b.total_length), BOS.len());
+                assert_eq!(b.num_device_caps, 5);
+            }
+            d => panic!("{:?}", d),
+        }
+        match cap(it.next()) {
+            DeviceCapability::Usb2Extension(e) => {
+                assert!(e.lpm() && e.besl());
+                assert_eq!(e.baseline_besl(), Some(4));
+                assert_eq!(e.deep_besl(), Some(6));
+            }
+            c => panic!("{:?}", c),
+        }
+        match cap(it.next()) {
+            DeviceCapability::SuperSpeed(s) => {
+                assert!(!s.low_speed() && s.full_speed() && s.high_speed() && s.gen1());
+                assert_eq!(s.u1_exit_latency, 10);
+                assert_eq!(s.u2_exit_latency, 0x7ff);
+            }
+            c => panic!("{:?}", c),
+        }
+        match cap(it.next()) {
+            DeviceCapability::ContainerId(c) => assert_eq!(c.id.0[15], 0xff),
+            c => panic!("{:?}", c),
+        }
+        match cap(it.next()) {
+            DeviceCapability::Platform(p) => {
+                extern crate std;
+                assert_eq!(
+                    std::format!("{:?}", p.id),
+                    "d8dd60df-4589-4cc7-9cd2-659d9e648a9f"
+                );
+                assert_eq!(p.data, &[0x00, 0x00, 0x03, 0x06, 0xb2, 0x00, 0x01, 0x00]);
+            }
+            c => panic!("{:?}", c),
+        }
+        match cap(it.next()) {
+            DeviceCapability::SuperSpeedPlus(s) => {
+                assert_eq!(s.sublink_speed_id_count(), 1);
+                assert_eq!((s.min_rx_lanes(), s.min_tx_lanes()), (1, 1));
+                let mut l = s.sublink_speeds();
+                assert_eq!(l.len(), 2);
+                let rx = l.next().unwrap();
+                assert_eq!(rx.bits_per_second(), 10_000_000_000);
+                assert_eq!(rx.protocol(), LinkProtocol::SuperSpeedPlus);
+                assert!(!rx.asymmetric() && !rx.transmit());
+                assert!(l.next().unwrap().transmit());
+            }
+            c => panic!("{:?}", c),
+        }
+        assert!(it.next().is_none());
+    }
+}
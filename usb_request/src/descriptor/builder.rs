@@ -0,0 +1,444 @@
+use super::{
+    Configuration, Device, Endpoint, EndpointTransfer, Hid, Interface, InterfaceAssociation,
+    SuperSpeedCompanion,
+};
+use crate::Speed;
+
+/// Builds a configuration descriptor with all its interface, endpoint and class-specific
+/// descriptors.
+///
+/// `total_length`, `num_interfaces` and `num_endpoints` are computed. Invalid descriptors panic,
+/// which fails compilation in a constant. Use [`configuration!`](crate::configuration) to get a
+/// byte array of the right size. All descriptors together may take at most 1024 bytes.
+///
+/// ```compile_fail
+/// # use usb_request::{descriptor::*, Speed};
+/// // Interrupt endpoints can't send more than 64 bytes at full speed.
+/// usb_request::configuration!(
+///     ConfigurationBuilder::new(Speed::Full, Configuration {
+///         total_length: 0,
+///         num_interfaces: 0,
+///         configuration_value: 1,
+///         index_configuration: 0,
+///         attributes: ConfigurationAttributes::new(false, false),
+///         max_power: 50,
+///     })
+///     .interface(Interface {
+///         number: 0,
+///         alternate_setting: 0,
+///         num_endpoints: 0,
+///         class: 0xff,
+///         subclass: 0,
+///         protocol: 0,
+///         index: 0,
+///     })
+///     .endpoint(Endpoint::new(
+///         EndpointAddress::new(EndpointNumber::N1, Direction::In),
+///         EndpointTransfer::Interrupt,
+///         MaxPacketSize::new(65, 0),
+///         10,
+///     ))
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigurationBuilder {
+    speed: Speed,
+    buf: [u8; 1024],
+    len: usize,
+    num_interfaces: u8,
+    /// Offset of the current interface descriptor, 0 if there is none.
+    interface: usize,
+    /// Endpoints of previous interfaces.
+    other_endpoints: u32,
+    /// Endpoints of all alternate settings of the current interface.
+    interface_endpoints: u32,
+    /// Endpoints of the current alternate setting.
+    setting_endpoints: u32,
+    /// A SuperSpeed endpoint still needs its companion descriptor.
+    needs_companion: bool,
+    /// The interface after the last association.
+    association_end: u16,
+}
+
+impl ConfigurationBuilder {
+    pub const fn new(speed: Speed, configuration: Configuration) -> Self {
+        Self {
+            speed,
+            buf: [0; 1024],
+            len: 0,
+            num_interfaces: 0,
+            interface: 0,
+            other_endpoints: 0,
+            interface_endpoints: 0,
+            setting_endpoints: 0,
+            needs_companion: false,
+            association_end: 0,
+        }
+        .append(&configuration.to_bytes())
+    }
+
+    /// Add an interface association, which must be followed by all interfaces of the function.
+    pub const fn association(mut self, association: InterfaceAssociation) -> Self {
+        assert!(
+            !self.needs_companion,
+            "missing SuperSpeed endpoint companion"
+        );
+        assert!(
+            self.num_interfaces as u16 >= self.association_end,
+            "association is missing interfaces"
+        );
+        assert!(
+            association.first_interface == self.num_interfaces,
+            "association must precede its first interface"
+        );
+        assert!(association.interface_count > 0, "empty association");
+        self.association_end =
+            association.first_interface as u16 + association.interface_count as u16;
+        self.append(&association.to_bytes())
+    }
+
+    /// Add an interface or alternate setting.
+    ///
+    /// Interfaces are numbered from 0 and each alternate setting must directly follow the
+    /// previous one.
+    pub const fn interface(mut self, interface: Interface) -> Self {
+        assert!(
+            !self.needs_companion,
+            "missing SuperSpeed endpoint companion"
+        );
+        if interface.alternate_setting == 0 {
+            assert!(
+                interface.number == self.num_interfaces,
+                "interfaces must be numbered consecutively from 0"
+            );
+            self.num_interfaces += 1;
+            self.other_endpoints |= self.interface_endpoints;
+            self.interface_endpoints = 0;
+        } else {
+            assert!(self.interface != 0, "alternate setting without interface");
+            assert!(
+                interface.number == self.buf[self.interface + 2]
+                    && interface.alternate_setting == self.buf[self.interface + 3].wrapping_add(1),
+                "alternate settings must be numbered consecutively and follow their interface"
+            );
+        }
+        self.setting_endpoints = 0;
+        self.interface = self.len;
+        self = self.append(&interface.to_bytes());
+        self.buf[self.interface + 4] = 0;
+        self
+    }
+
+    pub const fn endpoint(mut self, endpoint: Endpoint) -> Self {
+        assert!(
+            !self.needs_companion,
+            "missing SuperSpeed endpoint companion"
+        );
+        assert!(self.interface != 0, "endpoint without interface");
+        let b = endpoint.to_audio_bytes();
+        assert!(b[2] & 0xf != 0, "endpoint 0 has no descriptor");
+        let bit = endpoint.address.bit();
+        assert!(
+            self.other_endpoints & bit == 0,
+            "endpoint used by another interface"
+        );
+        assert!(self.setting_endpoints & bit == 0, "duplicate endpoint");
+        check_endpoint(self.speed, &endpoint);
+        self.setting_endpoints |= bit;
+        self.interface_endpoints |= bit;
+        self.buf[self.interface + 4] += 1;
+        self.needs_companion = matches!(self.speed, Speed::Super | Speed::SuperPlus);
+        match endpoint.audio {
+            Some(_) => self.append(&b),
+            None => self.append(&endpoint.to_bytes()),
+        }
+    }
+
+    /// Add the companion of the preceding SuperSpeed endpoint.
+    pub const fn companion(mut self, companion: SuperSpeedCompanion) -> Self {
+        assert!(
+            self.needs_companion,
+            "companion without SuperSpeed endpoint"
+        );
+        self.needs_companion = false;
+        self.append(&companion.to_bytes())
+    }
+
+    pub const fn hid(self, hid: Hid) -> Self {
+        self.class(&hid.to_bytes())
+    }
+
+    /// Add a class-specific descriptor.
+    pub const fn class(self, descriptor: &[u8]) -> Self {
+        assert!(
+            !self.needs_companion,
+            "missing SuperSpeed endpoint companion"
+        );
+        assert!(
+            descriptor.len() >= 2 && descriptor[0] as usize == descriptor.len(),
+            "invalid descriptor length"
+        );
+        self.append(descriptor)
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn bytes(&self) -> &[u8] {
+        assert!(
+            !self.needs_companion,
+            "missing SuperSpeed endpoint companion"
+        );
+        assert!(
+            self.num_interfaces as u16 >= self.association_end,
+            "association is missing interfaces"
+        );
+        self.buf.split_at(self.len).0
+    }
+
+    /// Copy the descriptors into an array, which must be exactly [`Self::len`] bytes long.
+    pub const fn build<const N: usize>(&self) -> [u8; N] {
+        let b = self.bytes();
+        assert!(b.len() == N, "wrong array size");
+        let mut a = [0; N];
+        let mut i = 0;
+        while i < N {
+            a[i] = b[i];
+            i += 1;
+        }
+        a
+    }
+
+    const fn append(mut self, b: &[u8]) -> Self {
+        assert!(
+            self.len + b.len() <= self.buf.len(),
+            "configuration too large"
+        );
+        let mut i = 0;
+        while i < b.len() {
+            self.buf[self.len + i] = b[i];
+            i += 1;
+        }
+        self.len += b.len();
+        let [l0, l1] = (self.len as u16).to_le_bytes();
+        self.buf[2] = l0;
+        self.buf[3] = l1;
+        self.buf[4] = self.num_interfaces;
+        self
+    }
+}
+
+/// Build a configuration descriptor as a `&'static [u8; N]`, see [`ConfigurationBuilder`].
+#[macro_export]
+macro_rules! configuration {
+    ($builder:expr) => {{
+        const BUILDER: $crate::descriptor::ConfigurationBuilder = $builder;
+        const BYTES: [u8; BUILDER.len()] = BUILDER.build();
+        &BYTES
+    }};
+}
+
+/// Encode a device descriptor, checking `max_packet_size_0` against `speed`.
+pub const fn build_device(speed: Speed, device: &Device) -> [u8; 18] {
+    assert!(
+        match speed {
+            Speed::Low => device.max_packet_size_0 == 8,
+            Speed::Full => matches!(device.max_packet_size_0, 8 | 16 | 32 | 64),
+            Speed::High => device.max_packet_size_0 == 64,
+            Speed::Super | Speed::SuperPlus => device.max_packet_size_0 == 9,
+        },
+        "invalid max_packet_size_0 for speed"
+    );
+    assert!(device.num_configurations > 0, "no configurations");
+    device.to_bytes()
+}
+
+const fn check_endpoint(speed: Speed, endpoint: &Endpoint) {
+    let transfer = endpoint.attributes.transfer();
+    assert!(
+        !matches!(
+            (speed, transfer),
+            (Speed::Low, EndpointTransfer::Bulk | EndpointTransfer::Isoch)
+        ),
+        "low-speed devices only support control and interrupt"
+    );
+    assert!(
+        speed.max_packet_size_valid(transfer, endpoint.max_packet_size),
+        "invalid max packet size for speed and transfer type"
+    );
+    assert!(
+        speed.interval_valid(transfer, endpoint.interval),
+        "invalid interval for speed and transfer type"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::descriptor::{
+        ConfigurationAttributes, ConfigurationTree, Direction, EndpointAddress, EndpointNumber,
+        MaxPacketSize,
+    };
+
+    const fn interface(number: u8, alternate_setting: u8) -> Interface {
+        Interface {
+            number,
+            alternate_setting,
+            num_endpoints: 0,
+            class: 3,
+            subclass: 1,
+            protocol: 1,
+            index: 0,
+        }
+    }
+
+    const CONFIGURATION: Configuration = Configuration {
+        total_length: 0,
+        num_interfaces: 0,
+        configuration_value: 1,
+        index_configuration: 0,
+        attributes: ConfigurationAttributes::new(false, true),
+        max_power: 50,
+    };
+
+    #[test]
+    fn keyboard() {
+        let b: &[u8] = crate::configuration!(ConfigurationBuilder::new(Speed::Full, CONFIGURATION)
+            .interface(interface(0, 0))
+            .hid(Hid {
+                hid_version: 0x0111,
+                country_code: 0,
+                num_descriptors: 1,
+                ty: 0x22,
+                len: 63,
+            })
+            .endpoint(Endpoint::new(
+                EndpointAddress::new(EndpointNumber::N1, Direction::In),
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(8, 0),
+                10,
+            )));
+        assert_eq!(
+            b,
+            [
+                0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x32, // configuration
+                0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface
+                0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // hid
+                0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+            ]
+        );
+    }
+
+    #[test]
+    fn counts() {
+        const B: ConfigurationBuilder = ConfigurationBuilder::new(Speed::Super, CONFIGURATION)
+            .interface(interface(0, 0))
+            .interface(interface(0, 1))
+            .endpoint(Endpoint::new(
+                EndpointAddress::new(EndpointNumber::N1, Direction::In),
+                EndpointTransfer::Bulk,
+                MaxPacketSize::new(1024, 0),
+                10,
+            ))
+            .companion(SuperSpeedCompanion {
+                max_burst: 0,
+                attributes: 0,
+                bytes_per_interval: 0,
+            })
+            .endpoint(Endpoint::new(
+                EndpointAddress::new(EndpointNumber::N2, Direction::In),
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(8, 0),
+                10,
+            ))
+            .companion(SuperSpeedCompanion {
+                max_burst: 0,
+                attributes: 0,
+                bytes_per_interval: 8,
+            })
+            .interface(interface(1, 0));
+        let t = ConfigurationTree::new(B.bytes()).unwrap();
+        t.check().unwrap();
+        assert_eq!(t.configuration().total_length as usize, B.len());
+        assert_eq!(t.configuration().num_interfaces, 2);
+        assert_eq!(t.interface(0, 1).unwrap().interface().num_endpoints, 2);
+        assert_eq!(t.interface(1, 0).unwrap().interface().num_endpoints, 0);
+    }
+
+    #[test]
+    #[should_panic = "endpoint used by another interface"]
+    fn shared_endpoint() {
+        let e = Endpoint::new(
+            EndpointAddress::new(EndpointNumber::N1, Direction::In),
+            EndpointTransfer::Bulk,
+            MaxPacketSize::new(64, 0),
+            10,
+        );
+        ConfigurationBuilder::new(Speed::Full, CONFIGURATION)
+            .interface(interface(0, 0))
+            .endpoint(e)
+            .interface(interface(1, 0))
+            .endpoint(e);
+    }
+
+    #[test]
+    #[should_panic = "missing SuperSpeed endpoint companion"]
+    fn class_before_companion() {
+        ConfigurationBuilder::new(Speed::Super, CONFIGURATION)
+            .interface(interface(0, 0))
+            .endpoint(Endpoint::new(
+                EndpointAddress::new(EndpointNumber::N1, Direction::In),
+                EndpointTransfer::Bulk,
+                MaxPacketSize::new(1024, 0),
+                10,
+            ))
+            .class(&[0x04, 0x25, 0x01, 0x00]);
+    }
+
+    #[test]
+    #[should_panic = "association is missing interfaces"]
+    fn short_association() {
+        ConfigurationBuilder::new(Speed::Full, CONFIGURATION)
+            .association(InterfaceAssociation {
+                first_interface: 0,
+                interface_count: 2,
+                function_class: 2,
+                function_subclass: 2,
+                function_protocol: 0,
+                index: 0,
+            })
+            .interface(interface(0, 0))
+            .bytes();
+    }
+
+    const DEVICE: Device = Device {
+        usb: 0x0200,
+        class: 0,
+        subclass: 0,
+        protocol: 0,
+        max_packet_size_0: 64,
+        vendor: 0x1234,
+        product: 0x5678,
+        device: 0x0100,
+        index_manufacturer: 0,
+        index_product: 0,
+        index_serial_number: 0,
+        num_configurations: 1,
+    };
+
+    #[test]
+    fn device() {
+        assert_eq!(build_device(Speed::High, &DEVICE), DEVICE.to_bytes());
+    }
+
+    #[test]
+    #[should_panic = "invalid max_packet_size_0 for speed"]
+    fn device_max_packet_size() {
+        build_device(Speed::Low, &DEVICE);
+    }
+}
@@ -0,0 +1,115 @@
+/// SuperSpeed Endpoint Companion, which follows every endpoint of a SuperSpeed device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuperSpeedCompanion {
+    /// The amount of additional packets the endpoint can send or receive in a burst.
+    pub max_burst: u8,
+    pub attributes: u8,
+    /// The total amount of bytes transferred every service interval.
+    ///
+    /// Only valid for periodic endpoints.
+    pub bytes_per_interval: u16,
+}
+
+impl SuperSpeedCompanion {
+    pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidSuperSpeedCompanion> {
+        if let &[a, b, c, d, ..] = buf {
+            Ok(SuperSpeedCompanion {
+                max_burst: a,
+                attributes: b,
+                bytes_per_interval: u16::from_le_bytes([c, d]),
+            })
+        } else {
+            Err(InvalidSuperSpeedCompanion::UnexpectedLength)
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 6] {
+        let [b0, b1] = self.bytes_per_interval.to_le_bytes();
+        [
+            6,
+            super::SUPERSPEED_COMPANION,
+            self.max_burst,
+            self.attributes,
+            b0,
+            b1,
+        ]
+    }
+
+    write_into!();
+
+    /// The raw MaxStreams field, i.e. the base 2 logarithm of the amount of streams.
+    ///
+    /// Only valid for bulk endpoints.
+    pub fn max_streams_exponent(&self) -> u8 {
+        self.attributes & 0x1f
+    }
+
+    /// The maximum amount of streams, or 0 if streams are not supported.
+    ///
+    /// Only valid for bulk endpoints.
+    pub fn max_streams(&self) -> u32 {
+        match self.max_streams_exponent() {
+            0 => 0,
+            n => 1 << n,
+        }
+    }
+
+    /// The amount of additional bursts per service interval.
+    ///
+    /// Only valid for isochronous endpoints.
+    pub const fn mult(&self) -> u8 {
+        self.attributes & 0x3
+    }
+
+    /// Whether a SuperSpeedPlus Isochronous Endpoint Companion follows this descriptor.
+    ///
+    /// Only valid for isochronous endpoints.
+    pub const fn ssp_isoch_companion(&self) -> bool {
+        self.attributes & 1 << 7 != 0
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidSuperSpeedCompanion {
+    UnexpectedLength,
+}
+
+/// SuperSpeedPlus Isochronous Endpoint Companion, for isochronous endpoints that need more
+/// than 48K per service interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuperSpeedPlusIsochCompanion {
+    pub bytes_per_interval: u32,
+}
+
+impl SuperSpeedPlusIsochCompanion {
+    pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidSuperSpeedPlusIsochCompanion> {
+        if let &[_, _, a, b, c, d, ..] = buf {
+            Ok(SuperSpeedPlusIsochCompanion {
+                bytes_per_interval: u32::from_le_bytes([a, b, c, d]),
+            })
+        } else {
+            Err(InvalidSuperSpeedPlusIsochCompanion::UnexpectedLength)
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 8] {
+        let [b0, b1, b2, b3] = self.bytes_per_interval.to_le_bytes();
+        [
+            8,
+            super::SUPERSPEEDPLUS_ISOCH_COMPANION,
+            0,
+            0,
+            b0,
+            b1,
+            b2,
+            b3,
+        ]
+    }
+
+    write_into!();
+}
+
+#[derive(Debug)]
+pub enum InvalidSuperSpeedPlusIsochCompanion {
+    UnexpectedLength,
+}
@@ -1,6 +1,6 @@
 use core::fmt;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Configuration {
     pub total_length: u16,
     pub num_interfaces: u8,
@@ -14,7 +14,7 @@ pub struct Configuration {
 
 impl Configuration {
     pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidConfiguration> {
-        if let &[a, b, c, d, e, f, g] = buf {
+        if let &[a, b, c, d, e, f, g, ..] = buf {
             Ok(Configuration {
                 total_length: u16::from_le_bytes([a, b]),
                 num_interfaces: c,
@@ -27,13 +27,40 @@ impl Configuration {
             Err(InvalidConfiguration::UnexpectedLength)
         }
     }
+
+    pub const fn to_bytes(&self) -> [u8; 9] {
+        self.encode(super::CONFIGURATION)
+    }
+
+    /// Encode as an other speed configuration descriptor.
+    pub const fn to_other_speed_bytes(&self) -> [u8; 9] {
+        self.encode(super::OTHER_SPEED_CONFIGURATION)
+    }
+
+    const fn encode(&self, ty: u8) -> [u8; 9] {
+        let [l0, l1] = self.total_length.to_le_bytes();
+        [
+            9,
+            ty,
+            l0,
+            l1,
+            self.num_interfaces,
+            self.configuration_value,
+            self.index_configuration,
+            self.attributes.0,
+            self.max_power,
+        ]
+    }
+
+    write_into!();
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ConfigurationAttributes(u8);
 
 macro_rules! flag {
     ($i:literal $f:ident) => {
-        fn $f(&self) -> bool {
+        pub const fn $f(&self) -> bool {
             self.0 & 1 << $i != 0
         }
     };
@@ -42,6 +69,11 @@ macro_rules! flag {
 impl ConfigurationAttributes {
     flag!(6 self_powered);
     flag!(5 remote_wakeup);
+
+    pub const fn new(self_powered: bool, remote_wakeup: bool) -> Self {
+        // Bit 7 is reserved and must be set.
+        Self(1 << 7 | (self_powered as u8) << 6 | (remote_wakeup as u8) << 5)
+    }
 }
 
 impl fmt::Debug for ConfigurationAttributes {
@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use crate::class::ClassCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Device {
     pub usb: u16,
     pub class: u8,
@@ -16,7 +18,7 @@ pub struct Device {
 
 impl Device {
     pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidDevice> {
-        if buf.len() != 16 {
+        if buf.len() < 16 {
             return Err(InvalidDevice::UnexpectedLength);
         }
         let f1 = |i: usize| buf[i - 2];
@@ -36,9 +38,136 @@ impl Device {
             num_configurations: f1(17),
         })
     }
+
+    pub const fn to_bytes(&self) -> [u8; 18] {
+        let [u0, u1] = self.usb.to_le_bytes();
+        let [v0, v1] = self.vendor.to_le_bytes();
+        let [p0, p1] = self.product.to_le_bytes();
+        let [d0, d1] = self.device.to_le_bytes();
+        [
+            18,
+            super::DEVICE,
+            u0,
+            u1,
+            self.class,
+            self.subclass,
+            self.protocol,
+            self.max_packet_size_0,
+            v0,
+            v1,
+            p0,
+            p1,
+            d0,
+            d1,
+            self.index_manufacturer,
+            self.index_product,
+            self.index_serial_number,
+            self.num_configurations,
+        ]
+    }
+
+    write_into!();
+
+    pub const fn class_code(&self) -> ClassCode {
+        ClassCode::new(self.class, self.subclass, self.protocol)
+    }
+
+    /// The device descriptor as it would be at the other speed.
+    pub fn at_other_speed(&self, q: &DeviceQualifier) -> Device {
+        Device {
+            usb: q.usb,
+            class: q.class,
+            subclass: q.subclass,
+            protocol: q.protocol,
+            max_packet_size_0: q.max_packet_size_0,
+            num_configurations: q.num_configurations,
+            ..*self
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum InvalidDevice {
     UnexpectedLength,
+    UnexpectedType,
+}
+
+/// The first 8 bytes of a device descriptor.
+///
+/// This is all a host can read before it knows the maximum packet size of endpoint 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DevicePrefix {
+    pub usb: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size_0: u8,
+}
+
+impl DevicePrefix {
+    /// Decode the start of a device descriptor, including its length and type.
+    pub fn decode(buf: &[u8]) -> Result<Self, InvalidDevice> {
+        match *buf {
+            [_, super::DEVICE, a, b, c, d, e, f, ..] => Ok(DevicePrefix {
+                usb: u16::from_le_bytes([a, b]),
+                class: c,
+                subclass: d,
+                protocol: e,
+                max_packet_size_0: f,
+            }),
+            [_, super::DEVICE, ..] => Err(InvalidDevice::UnexpectedLength),
+            _ => Err(InvalidDevice::UnexpectedType),
+        }
+    }
+}
+
+/// Describes how a high-speed capable device would operate at the other speed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceQualifier {
+    pub usb: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size_0: u8,
+    pub num_configurations: u8,
+}
+
+impl DeviceQualifier {
+    pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidDeviceQualifier> {
+        if let &[a, b, c, d, e, f, g, _, ..] = buf {
+            Ok(DeviceQualifier {
+                usb: u16::from_le_bytes([a, b]),
+                class: c,
+                subclass: d,
+                protocol: e,
+                max_packet_size_0: f,
+                num_configurations: g,
+            })
+        } else {
+            Err(InvalidDeviceQualifier::UnexpectedLength)
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 10] {
+        let [u0, u1] = self.usb.to_le_bytes();
+        [
+            10,
+            super::DEVICE_QUALIFIER,
+            u0,
+            u1,
+            self.class,
+            self.subclass,
+            self.protocol,
+            self.max_packet_size_0,
+            self.num_configurations,
+            0,
+        ]
+    }
+
+    write_into!();
+}
+
+#[derive(Debug)]
+pub enum InvalidDeviceQualifier {
+    UnexpectedLength,
 }
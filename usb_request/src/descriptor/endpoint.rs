@@ -1,34 +1,125 @@
 use core::fmt;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Endpoint {
     /// The address of the endpoint on the USB device described by this descriptor.
     pub address: EndpointAddress,
     pub attributes: EndpointAttributes,
-    pub max_packet_size: u16,
+    pub max_packet_size: MaxPacketSize,
     pub interval: u8,
+    /// The fields added by the audio class 1.0, present if the descriptor is 9 bytes long.
+    pub audio: Option<AudioEndpoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioEndpoint {
+    /// The rate at which feedback is provided, as a power of 2 in frames.
+    pub refresh: u8,
+    /// The address of the endpoint used for synchronization, 0 if none.
+    pub synch_address: u8,
 }
 
 impl Endpoint {
+    /// A data endpoint without synchronization or audio fields.
+    pub const fn new(
+        address: EndpointAddress,
+        transfer: EndpointTransfer,
+        max_packet_size: MaxPacketSize,
+        interval: u8,
+    ) -> Self {
+        Self {
+            address,
+            attributes: EndpointAttributes::new(transfer, EndpointSync::None, EndpointUsage::Data),
+            max_packet_size,
+            interval,
+            audio: None,
+        }
+    }
+
     pub(crate) fn from_raw(buf: &[u8]) -> Result<Endpoint, InvalidEndpoint> {
-        if let &[a, b, c, d, e] = buf {
+        if let &[a, b, c, d, e, ref rest @ ..] = buf {
             Ok(Endpoint {
-                address: EndpointAddress::from_raw(a).ok_or(InvalidEndpoint::InvalidAddress)?,
+                address: EndpointAddress::from_raw(a)
+                    .filter(|a| a.number() != EndpointNumber::N0)
+                    .ok_or(InvalidEndpoint::InvalidAddress)?,
                 attributes: EndpointAttributes::from_raw(b)
                     .ok_or(InvalidEndpoint::InvalidAttributes)?,
-                max_packet_size: u16::from_le_bytes([c, d]),
+                max_packet_size: MaxPacketSize(u16::from_le_bytes([c, d])),
                 interval: e,
+                audio: match *rest {
+                    [refresh, synch_address, ..] => Some(AudioEndpoint {
+                        refresh,
+                        synch_address,
+                    }),
+                    _ => None,
+                },
             })
         } else {
             Err(InvalidEndpoint::UnexpectedLength)
         }
     }
+
+    /// Encode without the audio fields.
+    pub const fn to_bytes(&self) -> [u8; 7] {
+        let [m0, m1] = self.max_packet_size.0.to_le_bytes();
+        [
+            7,
+            super::ENDPOINT,
+            self.address.0,
+            self.attributes.0,
+            m0,
+            m1,
+            self.interval,
+        ]
+    }
+
+    /// Encode with the audio fields, which are 0 if there are none.
+    pub const fn to_audio_bytes(&self) -> [u8; 9] {
+        let [_, _, b2, b3, b4, b5, b6] = self.to_bytes();
+        let (refresh, synch_address) = match self.audio {
+            Some(a) => (a.refresh, a.synch_address),
+            None => (0, 0),
+        };
+        [
+            9,
+            super::ENDPOINT,
+            b2,
+            b3,
+            b4,
+            b5,
+            b6,
+            refresh,
+            synch_address,
+        ]
+    }
+
+    /// Write the descriptor to the start of `buf`, with the audio fields if there are any.
+    ///
+    /// Returns the amount of bytes written or `None` if `buf` is too small.
+    pub fn write_into(&self, buf: &mut [u8]) -> Option<usize> {
+        let b = self.to_audio_bytes();
+        let b = match self.audio {
+            Some(_) => &b[..],
+            None => &self.to_bytes()[..],
+        };
+        buf.get_mut(..b.len())?.copy_from_slice(b);
+        Some(b.len())
+    }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct EndpointAddress(u8);
 
 impl EndpointAddress {
-    pub fn direction(&self) -> Direction {
+    pub const fn new(number: EndpointNumber, direction: Direction) -> Self {
+        let d = match direction {
+            Direction::Out => 0,
+            Direction::In => 1 << 7,
+        };
+        Self(d | number as u8)
+    }
+
+    pub const fn direction(&self) -> Direction {
         if self.0 & 1 << 7 == 0 {
             Direction::Out
         } else {
@@ -36,9 +127,10 @@ impl EndpointAddress {
         }
     }
 
-    pub fn number(&self) -> EndpointNumber {
+    pub const fn number(&self) -> EndpointNumber {
         use EndpointNumber::*;
         match self.0 & 0xf {
+            0 => N0,
             1 => N1,
             2 => N2,
             3 => N3,
@@ -58,8 +150,20 @@ impl EndpointAddress {
         }
     }
 
-    fn from_raw(n: u8) -> Option<Self> {
-        (1..=15).contains(&(n & 0xf)).then(|| Self(n))
+    /// A distinct bit for each of the 32 endpoint addresses, for sets of endpoints.
+    pub const fn bit(&self) -> u32 {
+        1 << ((self.0 & 0xf) + (self.0 >> 7) * 16)
+    }
+
+    pub(crate) fn from_raw(n: u8) -> Option<Self> {
+        // Bits 4 to 6 are reserved.
+        (n & 0x70 == 0).then_some(Self(n))
+    }
+}
+
+impl From<EndpointAddress> for u8 {
+    fn from(a: EndpointAddress) -> u8 {
+        a.0
     }
 }
 
@@ -72,51 +176,87 @@ impl fmt::Debug for EndpointAddress {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EndpointNumber {
-    N1,
-    N2,
-    N3,
-    N4,
-    N5,
-    N6,
-    N7,
-    N8,
-    N9,
-    N10,
-    N11,
-    N12,
-    N13,
-    N14,
-    N15,
+    /// The default control pipe.
+    N0 = 0,
+    N1 = 1,
+    N2 = 2,
+    N3 = 3,
+    N4 = 4,
+    N5 = 5,
+    N6 = 6,
+    N7 = 7,
+    N8 = 8,
+    N9 = 9,
+    N10 = 10,
+    N11 = 11,
+    N12 = 12,
+    N13 = 13,
+    N14 = 14,
+    N15 = 15,
 }
 
 impl From<EndpointNumber> for usize {
     fn from(n: EndpointNumber) -> usize {
-        use EndpointNumber::*;
-        match n {
-            N1 => 1,
-            N2 => 2,
-            N3 => 3,
-            N4 => 4,
-            N5 => 5,
-            N6 => 6,
-            N7 => 7,
-            N8 => 8,
-            N9 => 9,
-            N10 => 10,
-            N11 => 11,
-            N12 => 12,
-            N13 => 13,
-            N14 => 14,
-            N15 => 15,
-        }
+        n as usize
     }
 }
 
+/// The wMaxPacketSize field of an endpoint.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct MaxPacketSize(u16);
+
+impl MaxPacketSize {
+    /// `additional_transactions` per microframe are only allowed for high-speed periodic
+    /// endpoints.
+    pub const fn new(size: u16, additional_transactions: u8) -> Self {
+        assert!(size <= 0x7ff, "max packet size too large");
+        assert!(
+            additional_transactions <= 2,
+            "too many additional transactions"
+        );
+        Self(size | (additional_transactions as u16) << 11)
+    }
+
+    /// The maximum size of a single packet.
+    pub const fn size(&self) -> u16 {
+        self.0 & 0x7ff
+    }
+
+    pub const fn additional_transactions(&self) -> u8 {
+        (self.0 >> 11 & 0x3) as u8
+    }
+
+    /// The amount of bytes that can be transferred in a (micro)frame.
+    pub const fn bytes_per_interval(&self) -> u32 {
+        self.size() as u32 * (1 + self.additional_transactions() as u32)
+    }
+}
+
+impl From<MaxPacketSize> for u16 {
+    fn from(m: MaxPacketSize) -> u16 {
+        m.0
+    }
+}
+
+impl fmt::Debug for MaxPacketSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct(stringify!(MaxPacketSize))
+            .field("size", &self.size())
+            .field("additional_transactions", &self.additional_transactions())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct EndpointAttributes(u8);
 
 impl EndpointAttributes {
+    pub const fn new(transfer: EndpointTransfer, sync: EndpointSync, usage: EndpointUsage) -> Self {
+        Self((usage as u8) << 4 | (sync as u8) << 2 | transfer as u8)
+    }
+
     pub fn usage(&self) -> EndpointUsage {
         match self.0 >> 4 & 0x3 {
             0 => EndpointUsage::Data,
@@ -136,7 +276,7 @@ impl EndpointAttributes {
         }
     }
 
-    pub fn transfer(&self) -> EndpointTransfer {
+    pub const fn transfer(&self) -> EndpointTransfer {
         match self.0 & 0x3 {
             0 => EndpointTransfer::Control,
             1 => EndpointTransfer::Isoch,
@@ -147,7 +287,7 @@ impl EndpointAttributes {
     }
 
     fn from_raw(n: u8) -> Option<Self> {
-        matches!(n >> 4 & 0x3, 0 | 1 | 2).then(|| Self(n))
+        matches!(n >> 4 & 0x3, 0..=2).then_some(Self(n))
     }
 }
 
@@ -161,30 +301,30 @@ impl fmt::Debug for EndpointAttributes {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EndpointUsage {
-    Data,
-    Feedback,
-    Implicit,
+    Data = 0,
+    Feedback = 1,
+    Implicit = 2,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EndpointSync {
-    None,
-    Async,
-    Adapt,
-    Sync,
+    None = 0,
+    Async = 1,
+    Adapt = 2,
+    Sync = 3,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EndpointTransfer {
-    Control,
-    Isoch,
-    Bulk,
-    Interrupt,
+    Control = 0,
+    Isoch = 1,
+    Bulk = 2,
+    Interrupt = 3,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
     In,
     Out,
@@ -1,16 +1,22 @@
 use core::fmt;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Hid {
     pub hid_version: u16,
     pub country_code: u8,
     pub num_descriptors: u8,
+    /// Type of the first class descriptor, usually a report descriptor.
+    ///
+    /// The type and length of any further class descriptors are in [`Iter::trailing`].
+    ///
+    /// [`Iter::trailing`]: super::Iter::trailing
     pub ty: u8,
     pub len: u16,
 }
 
 impl Hid {
     pub(crate) fn from_raw(buf: &[u8]) -> Result<Hid, InvalidHid> {
-        if let &[a, b, c, d, e, f, g] = buf {
+        if let &[a, b, c, d, e, f, g, ..] = buf {
             Ok(Hid {
                 hid_version: u16::from_le_bytes([a, b]),
                 country_code: c,
@@ -22,6 +28,24 @@ impl Hid {
             Err(InvalidHid::UnexpectedLength)
         }
     }
+
+    pub const fn to_bytes(&self) -> [u8; 9] {
+        let [v0, v1] = self.hid_version.to_le_bytes();
+        let [l0, l1] = self.len.to_le_bytes();
+        [
+            9,
+            super::HID,
+            v0,
+            v1,
+            self.country_code,
+            self.num_descriptors,
+            self.ty,
+            l0,
+            l1,
+        ]
+    }
+
+    write_into!();
 }
 
 impl fmt::Debug for Hid {
@@ -1,4 +1,6 @@
-#[derive(Debug)]
+use crate::class::ClassCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Interface {
     pub number: u8,
     pub alternate_setting: u8,
@@ -11,7 +13,7 @@ pub struct Interface {
 
 impl Interface {
     pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidInterface> {
-        if let &[a, b, c, d, e, f, g] = buf {
+        if let &[a, b, c, d, e, f, g, ..] = buf {
             Ok(Interface {
                 number: a,
                 alternate_setting: b,
@@ -25,6 +27,26 @@ impl Interface {
             Err(InvalidInterface::UnexpectedLength)
         }
     }
+
+    pub const fn to_bytes(&self) -> [u8; 9] {
+        [
+            9,
+            super::INTERFACE,
+            self.number,
+            self.alternate_setting,
+            self.num_endpoints,
+            self.class,
+            self.subclass,
+            self.protocol,
+            self.index,
+        ]
+    }
+
+    write_into!();
+
+    pub const fn class_code(&self) -> ClassCode {
+        ClassCode::new(self.class, self.subclass, self.protocol)
+    }
 }
 
 #[derive(Debug)]
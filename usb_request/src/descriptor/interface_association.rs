@@ -0,0 +1,64 @@
+use crate::class::ClassCode;
+
+/// Groups interfaces that belong to a single function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InterfaceAssociation {
+    pub first_interface: u8,
+    pub interface_count: u8,
+    pub function_class: u8,
+    pub function_subclass: u8,
+    pub function_protocol: u8,
+    pub index: u8,
+}
+
+impl InterfaceAssociation {
+    pub(crate) fn from_raw(buf: &[u8]) -> Result<Self, InvalidInterfaceAssociation> {
+        if let &[a, b, c, d, e, f, ..] = buf {
+            Ok(InterfaceAssociation {
+                first_interface: a,
+                interface_count: b,
+                function_class: c,
+                function_subclass: d,
+                function_protocol: e,
+                index: f,
+            })
+        } else {
+            Err(InvalidInterfaceAssociation::UnexpectedLength)
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 8] {
+        [
+            8,
+            super::INTERFACE_ASSOCIATION,
+            self.first_interface,
+            self.interface_count,
+            self.function_class,
+            self.function_subclass,
+            self.function_protocol,
+            self.index,
+        ]
+    }
+
+    write_into!();
+
+    pub const fn class_code(&self) -> ClassCode {
+        ClassCode::new(
+            self.function_class,
+            self.function_subclass,
+            self.function_protocol,
+        )
+    }
+
+    /// Whether the given interface is part of this function.
+    pub fn contains(&self, interface: u8) -> bool {
+        let n = u16::from(interface);
+        let first = u16::from(self.first_interface);
+        (first..first + u16::from(self.interface_count)).contains(&n)
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidInterfaceAssociation {
+    UnexpectedLength,
+}
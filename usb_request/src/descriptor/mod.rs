@@ -1,25 +1,101 @@
+macro_rules! write_into {
+    () => {
+        /// Write the descriptor to the start of `buf`.
+        ///
+        /// Returns the amount of bytes written or `None` if `buf` is too small.
+        pub fn write_into(&self, buf: &mut [u8]) -> Option<usize> {
+            let b = self.to_bytes();
+            buf.get_mut(..b.len())?.copy_from_slice(&b);
+            Some(b.len())
+        }
+    };
+}
+
+mod bos;
+mod builder;
+mod companion;
 mod configuration;
 mod device;
 mod endpoint;
 mod hid;
 mod interface;
+mod interface_association;
 mod string;
+mod tree;
+mod validate;
 
+pub use bos::*;
+pub use builder::*;
+pub use companion::*;
 pub use configuration::*;
 pub use device::*;
 pub use endpoint::*;
 pub use hid::*;
 pub use interface::*;
+pub use interface_association::*;
 pub use string::*;
+pub use tree::*;
+pub use validate::*;
 
 use core::mem;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum GetDescriptor {
     Device,
-    Configuration { index: u8 },
-    String { index: u8 },
-    Report,
+    /// Read a configuration descriptor.
+    ///
+    /// If `total_length` is `None` only the configuration descriptor itself is returned, which
+    /// can be used to determine the `total_length` of the configuration and all its interface
+    /// and endpoint descriptors.
+    Configuration {
+        index: u8,
+        total_length: Option<u16>,
+    },
+    /// Read the table of supported languages, i.e. string index 0.
+    Languages,
+    /// Read a string in the given language.
+    ///
+    /// `index` must not be 0.
+    String {
+        index: u8,
+        language: LanguageId,
+    },
+    Report {
+        interface: u8,
+        length: u16,
+    },
+    /// Read the Binary device Object Store.
+    ///
+    /// If `total_length` is `None` only the BOS descriptor itself is returned.
+    Bos {
+        total_length: Option<u16>,
+    },
+    /// Read how the device would be described at the other speed.
+    ///
+    /// This is only supported by high-speed capable devices.
+    DeviceQualifier,
+    /// Read a configuration as it would be at the other speed.
+    ///
+    /// If `total_length` is `None` only the configuration descriptor itself is returned.
+    OtherSpeedConfiguration {
+        index: u8,
+        total_length: Option<u16>,
+    },
+}
+
+impl GetDescriptor {
+    /// The default amount of bytes to request.
+    pub fn length(&self) -> u16 {
+        match self {
+            Self::Device => 18,
+            Self::Configuration { total_length, .. } => total_length.unwrap_or(9),
+            Self::Languages | Self::String { .. } => 255,
+            Self::Report { length, .. } => *length,
+            Self::Bos { total_length } => total_length.unwrap_or(5),
+            Self::DeviceQualifier => 10,
+            Self::OtherSpeedConfiguration { total_length, .. } => total_length.unwrap_or(9),
+        }
+    }
 }
 
 pub(crate) const DEVICE: u8 = 0x1;
@@ -27,12 +103,15 @@ pub(crate) const CONFIGURATION: u8 = 0x2;
 pub(crate) const STRING: u8 = 0x3;
 pub(crate) const INTERFACE: u8 = 0x4;
 pub(crate) const ENDPOINT: u8 = 0x5;
-#[allow(dead_code)]
 pub(crate) const DEVICE_QUALIFIER: u8 = 0x6;
-#[allow(dead_code)]
 pub(crate) const OTHER_SPEED_CONFIGURATION: u8 = 0x7;
 #[allow(dead_code)]
 pub(crate) const INTERFACE_POWER: u8 = 0x8;
+pub(crate) const INTERFACE_ASSOCIATION: u8 = 0xb;
+pub(crate) const BOS: u8 = 0xf;
+pub(crate) const DEVICE_CAPABILITY: u8 = 0x10;
+pub(crate) const SUPERSPEED_COMPANION: u8 = 0x30;
+pub(crate) const SUPERSPEEDPLUS_ISOCH_COMPANION: u8 = 0x31;
 
 pub(crate) const HID: u8 = 0x21;
 pub(crate) const REPORT: u8 = 0x22;
@@ -43,10 +122,17 @@ pub(crate) const PHYSICAL: u8 = 0x23;
 pub enum Descriptor<'a> {
     Device(Device),
     Configuration(Configuration),
+    DeviceQualifier(DeviceQualifier),
+    OtherSpeedConfiguration(Configuration),
     String(StringIter<'a>),
     Interface(Interface),
     Endpoint(Endpoint),
     Hid(Hid),
+    InterfaceAssociation(InterfaceAssociation),
+    Bos(Bos),
+    DeviceCapability(DeviceCapability<'a>),
+    SuperSpeedCompanion(SuperSpeedCompanion),
+    SuperSpeedPlusIsochCompanion(SuperSpeedPlusIsochCompanion),
     Unknown { ty: u8, data: &'a [u8] },
 }
 
@@ -67,63 +153,57 @@ impl<'a> Descriptor<'a> {
     into!(Configuration into_configuration Configuration);
 }
 
-#[derive(Debug)]
-pub struct StringIter<'a>(&'a [[u8; 2]]);
-
-impl<'a> StringIter<'a> {
-    pub(crate) fn from_raw(data: &'a [u8]) -> Result<StringIter, InvalidString> {
-        let (s, rem) = data.as_chunks();
-        rem.is_empty()
-            .then(|| Self(s))
-            .ok_or(InvalidString::UnexpectedLength)
-    }
-}
-
-impl Iterator for StringIter<'_> {
-    type Item = u16;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.split_first().map(|(c, s)| {
-            self.0 = s;
-            u16::from_le_bytes(*c)
-        })
-    }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.len(), Some(self.len()))
+pub fn decode(buf: &[u8]) -> Iter<'_> {
+    Iter {
+        buf,
+        trailing: &[],
+        lenient: false,
     }
 }
 
-impl ExactSizeIterator for StringIter<'_> {
-    fn len(&self) -> usize {
-        self.0.len()
+/// Like [`decode`], but continue after descriptors that fail to parse.
+///
+/// Only an invalid length ends iteration, since the next descriptor can't be found.
+pub fn decode_lenient(buf: &[u8]) -> Iter<'_> {
+    Iter {
+        buf,
+        trailing: &[],
+        lenient: true,
     }
 }
 
-#[derive(Debug)]
-pub enum InvalidString {
-    UnexpectedLength,
-}
-
-pub fn decode(buf: &[u8]) -> Iter<'_> {
-    Iter { buf }
-}
-
+#[derive(Clone)]
 pub struct Iter<'a> {
     buf: &'a [u8],
+    trailing: &'a [u8],
+    lenient: bool,
+}
+
+impl<'a> Iter<'a> {
+    /// The bytes of the last decoded descriptor after the fields it was decoded into.
+    ///
+    /// These hold fields added by later revisions of the specification or by a class.
+    pub fn trailing(&self) -> &'a [u8] {
+        self.trailing
+    }
 }
 
 impl<'a> Iterator for Iter<'a> {
     type Item = Result<Descriptor<'a>, InvalidDescriptor>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.trailing = &[];
         (!self.buf.is_empty()).then(|| {
             let buf = mem::take(&mut self.buf);
             let l = buf[0];
             if l < 2 || usize::from(l) > buf.len() {
                 return Err(InvalidDescriptor::Truncated { length: l.max(2) });
             }
-            let b = &buf[2..usize::from(l)];
+            let (d, rest) = buf.split_at(l.into());
+            if self.lenient {
+                self.buf = rest;
+            }
+            let b = &d[2..];
             let r = match buf[1] {
                 DEVICE => {
                     Descriptor::Device(Device::from_raw(b).map_err(InvalidDescriptor::Device)?)
@@ -131,6 +211,12 @@ impl<'a> Iterator for Iter<'a> {
                 CONFIGURATION => Descriptor::Configuration(
                     Configuration::from_raw(b).map_err(InvalidDescriptor::Configuration)?,
                 ),
+                DEVICE_QUALIFIER => Descriptor::DeviceQualifier(
+                    DeviceQualifier::from_raw(b).map_err(InvalidDescriptor::DeviceQualifier)?,
+                ),
+                OTHER_SPEED_CONFIGURATION => Descriptor::OtherSpeedConfiguration(
+                    Configuration::from_raw(b).map_err(InvalidDescriptor::Configuration)?,
+                ),
                 STRING => {
                     Descriptor::String(StringIter::from_raw(b).map_err(InvalidDescriptor::String)?)
                 }
@@ -141,9 +227,41 @@ impl<'a> Iterator for Iter<'a> {
                     Endpoint::from_raw(b).map_err(InvalidDescriptor::Endpoint)?,
                 ),
                 HID => Descriptor::Hid(Hid::from_raw(b).map_err(InvalidDescriptor::Hid)?),
+                INTERFACE_ASSOCIATION => Descriptor::InterfaceAssociation(
+                    InterfaceAssociation::from_raw(b)
+                        .map_err(InvalidDescriptor::InterfaceAssociation)?,
+                ),
+                BOS => Descriptor::Bos(Bos::from_raw(b).map_err(InvalidDescriptor::Bos)?),
+                DEVICE_CAPABILITY => Descriptor::DeviceCapability(
+                    DeviceCapability::from_raw(b).map_err(InvalidDescriptor::DeviceCapability)?,
+                ),
+                SUPERSPEED_COMPANION => Descriptor::SuperSpeedCompanion(
+                    SuperSpeedCompanion::from_raw(b)
+                        .map_err(InvalidDescriptor::SuperSpeedCompanion)?,
+                ),
+                SUPERSPEEDPLUS_ISOCH_COMPANION => Descriptor::SuperSpeedPlusIsochCompanion(
+                    SuperSpeedPlusIsochCompanion::from_raw(b)
+                        .map_err(InvalidDescriptor::SuperSpeedPlusIsochCompanion)?,
+                ),
                 ty => Descriptor::Unknown { ty, data: b },
             };
-            self.buf = &buf[usize::from(l)..];
+            let base = match &r {
+                Descriptor::Device(_) => 18,
+                Descriptor::Configuration(_)
+                | Descriptor::OtherSpeedConfiguration(_)
+                | Descriptor::Interface(_)
+                | Descriptor::Hid(_)
+                | Descriptor::Endpoint(Endpoint { audio: Some(_), .. }) => 9,
+                Descriptor::DeviceQualifier(_) => 10,
+                Descriptor::Endpoint(_) => 7,
+                Descriptor::InterfaceAssociation(_)
+                | Descriptor::SuperSpeedPlusIsochCompanion(_) => 8,
+                Descriptor::SuperSpeedCompanion(_) => 6,
+                Descriptor::Bos(_) => 5,
+                _ => d.len(),
+            };
+            self.trailing = &d[base..];
+            self.buf = rest;
             Ok(r)
         })
     }
@@ -153,9 +271,213 @@ impl<'a> Iterator for Iter<'a> {
 pub enum InvalidDescriptor {
     Truncated { length: u8 },
     Device(InvalidDevice),
+    DeviceQualifier(InvalidDeviceQualifier),
     Configuration(InvalidConfiguration),
     String(InvalidString),
     Interface(InvalidInterface),
     Endpoint(InvalidEndpoint),
     Hid(InvalidHid),
+    InterfaceAssociation(InvalidInterfaceAssociation),
+    Bos(InvalidBos),
+    DeviceCapability(InvalidDeviceCapability),
+    SuperSpeedCompanion(InvalidSuperSpeedCompanion),
+    SuperSpeedPlusIsochCompanion(InvalidSuperSpeedPlusIsochCompanion),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Boot keyboard with a single interrupt IN endpoint.
+    const KEYBOARD_CONFIGURATION: &[u8] = &[
+        0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // hid
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+    ];
+
+    const KEYBOARD_DEVICE: &[u8] = &[
+        0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x34, 0x12, 0x78, 0x56, 0x00, 0x01, 0x01,
+        0x02, 0x00, 0x01,
+    ];
+
+    #[test]
+    fn round_trip_device() {
+        let d = decode(KEYBOARD_DEVICE)
+            .next()
+            .unwrap()
+            .unwrap()
+            .into_device()
+            .unwrap();
+        assert_eq!(d.vendor, 0x1234);
+        assert_eq!(d.to_bytes(), KEYBOARD_DEVICE);
+        let mut buf = [0; 18];
+        assert_eq!(d.write_into(&mut buf), Some(18));
+        assert_eq!(buf, KEYBOARD_DEVICE);
+        assert_eq!(d.write_into(&mut buf[..17]), None);
+    }
+
+    #[test]
+    fn device_qualifier() {
+        const QUALIFIER: &[u8] = &[0x0a, 0x06, 0x00, 0x02, 0xef, 0x02, 0x01, 0x08, 0x02, 0x00];
+        let Some(Ok(Descriptor::DeviceQualifier(q))) = decode(QUALIFIER).next() else {
+            panic!("not a device qualifier");
+        };
+        assert_eq!(q.to_bytes(), QUALIFIER);
+        let d = decode(KEYBOARD_DEVICE)
+            .next()
+            .unwrap()
+            .unwrap()
+            .into_device()
+            .unwrap();
+        let o = d.at_other_speed(&q);
+        assert_eq!((o.class, o.subclass, o.protocol), (0xef, 0x02, 0x01));
+        assert_eq!((o.max_packet_size_0, o.num_configurations), (8, 2));
+        assert_eq!((o.vendor, o.product, o.device), (0x1234, 0x5678, 0x0100));
+        assert_eq!(o.index_product, d.index_product);
+    }
+
+    #[test]
+    fn round_trip_configuration() {
+        let mut buf = [0; KEYBOARD_CONFIGURATION.len()];
+        let mut i = 0;
+        for d in decode(KEYBOARD_CONFIGURATION) {
+            let b = &mut buf[i..];
+            i += match d.unwrap() {
+                Descriptor::Configuration(c) => c.write_into(b),
+                Descriptor::Interface(c) => c.write_into(b),
+                Descriptor::Hid(c) => c.write_into(b),
+                Descriptor::Endpoint(c) => c.write_into(b),
+                d => panic!("{:?}", d),
+            }
+            .unwrap();
+        }
+        assert_eq!(buf, KEYBOARD_CONFIGURATION);
+    }
+
+    #[test]
+    fn lenient() {
+        let buf = [
+            0x06, 0x05, 0x81, 0x03, 0x08, 0x00, // short endpoint
+            0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface
+            0x04, 0x04, 0x00, // truncated
+        ];
+        let mut it = decode(&buf);
+        assert!(matches!(
+            it.next(),
+            Some(Err(InvalidDescriptor::Endpoint(_)))
+        ));
+        assert!(it.next().is_none());
+        let mut it = decode_lenient(&buf);
+        assert!(matches!(
+            it.next(),
+            Some(Err(InvalidDescriptor::Endpoint(_)))
+        ));
+        assert!(matches!(it.next(), Some(Ok(Descriptor::Interface(_)))));
+        assert!(matches!(
+            it.next(),
+            Some(Err(InvalidDescriptor::Truncated { length: 4 }))
+        ));
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn extended() {
+        let buf = [
+            0x09, 0x05, 0x01, 0x05, 0xc0, 0x00, 0x01, 0x00, 0x82, // audio endpoint
+            0x0c, 0x21, 0x11, 0x01, 0x00, 0x02, 0x22, 0x3f, 0x00, 0x23, 0x10, 0x00, // hid
+            0x0a, 0x04, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0xaa, // interface
+        ];
+        let mut it = decode(&buf);
+        let Some(Ok(Descriptor::Endpoint(e))) = it.next() else {
+            panic!()
+        };
+        let audio = AudioEndpoint {
+            refresh: 0,
+            synch_address: 0x82,
+        };
+        assert_eq!(e.audio, Some(audio));
+        assert!(it.trailing().is_empty());
+        let mut b = [0; 9];
+        assert_eq!(e.write_into(&mut b), Some(9));
+        assert_eq!(b, buf[..9]);
+        let Some(Ok(Descriptor::Hid(h))) = it.next() else {
+            panic!()
+        };
+        assert_eq!((h.num_descriptors, h.ty, h.len), (2, REPORT, 0x3f));
+        assert_eq!(it.trailing(), [PHYSICAL, 0x10, 0x00]);
+        assert!(matches!(it.next(), Some(Ok(Descriptor::Interface(_)))));
+        assert_eq!(it.trailing(), [0xaa]);
+        assert!(it.next().is_none());
+
+        let p = DevicePrefix::decode(&KEYBOARD_DEVICE[..8]).unwrap();
+        assert_eq!((p.usb, p.max_packet_size_0), (0x200, 64));
+        assert!(matches!(
+            DevicePrefix::decode(&KEYBOARD_DEVICE[..7]),
+            Err(InvalidDevice::UnexpectedLength)
+        ));
+        assert!(matches!(
+            DevicePrefix::decode(&KEYBOARD_CONFIGURATION[..8]),
+            Err(InvalidDevice::UnexpectedType)
+        ));
+    }
+
+    #[test]
+    fn endpoint() {
+        let ep0 = EndpointAddress::from_raw(0x80).unwrap();
+        assert_eq!(ep0.number(), EndpointNumber::N0);
+        assert_eq!(ep0.direction(), Direction::In);
+        assert!(EndpointAddress::from_raw(0x11).is_none());
+        assert!(matches!(
+            decode(&[0x07, 0x05, 0x00, 0x00, 0x40, 0x00, 0x00]).next(),
+            Some(Err(InvalidDescriptor::Endpoint(
+                InvalidEndpoint::InvalidAddress
+            )))
+        ));
+
+        let Some(Ok(Descriptor::Endpoint(e))) =
+            decode(&[0x07, 0x05, 0x81, 0x01, 0x00, 0x14, 0x01]).next()
+        else {
+            panic!()
+        };
+        let m = e.max_packet_size;
+        assert_eq!((m.size(), m.additional_transactions()), (1024, 2));
+        assert_eq!(m.bytes_per_interval(), 3072);
+        assert_eq!(m, MaxPacketSize::new(1024, 2));
+        assert_eq!(u16::from(m), 0x1400);
+    }
+
+    #[test]
+    fn round_trip_constructed() {
+        let e = Endpoint {
+            address: EndpointAddress::new(EndpointNumber::N3, Direction::Out),
+            attributes: EndpointAttributes::new(
+                EndpointTransfer::Isoch,
+                EndpointSync::Adapt,
+                EndpointUsage::Data,
+            ),
+            max_packet_size: MaxPacketSize::new(192, 0),
+            interval: 1,
+            audio: None,
+        };
+        let c = Configuration {
+            total_length: 25,
+            num_interfaces: 1,
+            configuration_value: 1,
+            index_configuration: 0,
+            attributes: ConfigurationAttributes::new(true, false),
+            max_power: 0,
+        };
+        let e_bytes = e.to_bytes();
+        let c_bytes = c.to_bytes();
+        assert_eq!(e_bytes, [7, 5, 0x03, 0x09, 192, 0, 1]);
+        match decode(&e_bytes).next() {
+            Some(Ok(Descriptor::Endpoint(d))) => assert_eq!(d, e),
+            d => panic!("{:?}", d),
+        }
+        match decode(&c_bytes).next() {
+            Some(Ok(Descriptor::Configuration(d))) => assert_eq!(d, c),
+            d => panic!("{:?}", d),
+        }
+    }
 }
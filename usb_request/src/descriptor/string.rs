@@ -1 +1,245 @@
+use core::{char, fmt};
 
+/// The code units of a string descriptor.
+///
+/// The descriptor at index 0 instead holds the supported languages, see [`StringIter::languages`].
+#[derive(Clone, Copy, Debug)]
+pub struct StringIter<'a>(&'a [[u8; 2]]);
+
+impl<'a> StringIter<'a> {
+    pub(crate) fn from_raw(data: &'a [u8]) -> Result<StringIter<'a>, InvalidString> {
+        let (s, rem) = data.as_chunks();
+        rem.is_empty()
+            .then_some(Self(s))
+            .ok_or(InvalidString::UnexpectedLength)
+    }
+
+    /// Interpret the descriptor as a table of language IDs.
+    pub fn languages(self) -> impl Iterator<Item = LanguageId> + 'a {
+        self.map(LanguageId)
+    }
+
+    /// Decode the string as UTF-16.
+    ///
+    /// Unpaired surrogates are replaced with [`char::REPLACEMENT_CHARACTER`].
+    pub fn chars(self) -> impl Iterator<Item = char> + 'a {
+        char::decode_utf16(self).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn to_string_lossy(self) -> alloc::string::String {
+        self.chars().collect()
+    }
+}
+
+impl fmt::Display for StringIter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars().try_for_each(|c| fmt::Write::write_char(f, c))
+    }
+}
+
+impl Iterator for StringIter<'_> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.split_first().map(|(c, s)| {
+            self.0 = s;
+            u16::from_le_bytes(*c)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len(), Some(self.len()))
+    }
+}
+
+impl ExactSizeIterator for StringIter<'_> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidString {
+    UnexpectedLength,
+}
+
+/// Encode `s` as a string descriptor into `buf`.
+///
+/// Strings longer than 126 UTF-16 code units are truncated. Returns the amount of bytes written,
+/// which is less than the descriptor length if `buf` is too small.
+pub fn encode_string(s: &str, buf: &mut [u8]) -> usize {
+    let mut units = 0;
+    let chars = s.chars().take_while(|c| {
+        units += c.len_utf16();
+        units <= 126
+    });
+    let mut it = [0, super::STRING].into_iter().chain(
+        chars
+            .flat_map(|c| {
+                let mut u = [0; 2];
+                let n = c.encode_utf16(&mut u).len();
+                u.into_iter().take(n)
+            })
+            .flat_map(u16::to_le_bytes),
+    );
+    let n = buf.iter_mut().zip(&mut it).map(|(b, v)| *b = v).count();
+    let length = n + it.count();
+    if let Some(b) = buf.first_mut() {
+        *b = length as u8;
+    }
+    n
+}
+
+/// Encode `languages` as the string descriptor at index 0 into `buf`.
+///
+/// Returns the amount of bytes written, like [`encode_string`].
+pub fn encode_languages(languages: &[LanguageId], buf: &mut [u8]) -> usize {
+    let languages = &languages[..languages.len().min(126)];
+    let it = [2 + 2 * languages.len() as u8, super::STRING]
+        .into_iter()
+        .chain(languages.iter().flat_map(|l| l.0.to_le_bytes()));
+    buf.iter_mut().zip(it).map(|(b, v)| *b = v).count()
+}
+
+macro_rules! languages {
+    ($($n:ident $v:literal $s:literal)*) => {
+        impl LanguageId {
+            $(pub const $n: Self = Self($v);)*
+
+            /// The name of the language, if known.
+            pub fn name(&self) -> Option<&'static str> {
+                Some(match self.0 {
+                    $($v => $s,)*
+                    _ => return None,
+                })
+            }
+        }
+    };
+}
+
+/// A USB language identifier (LANGID).
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LanguageId(pub u16);
+
+impl LanguageId {
+    /// The primary language, e.g. English.
+    pub fn primary(&self) -> u16 {
+        self.0 & 0x3ff
+    }
+
+    /// The sublanguage, e.g. United States.
+    pub fn sub(&self) -> u8 {
+        (self.0 >> 10) as u8
+    }
+}
+
+languages! {
+    ARABIC_SAUDI_ARABIA 0x0401 "Arabic (Saudi Arabia)"
+    CHINESE_TAIWAN 0x0404 "Chinese (Taiwan)"
+    CZECH 0x0405 "Czech"
+    DANISH 0x0406 "Danish"
+    GERMAN 0x0407 "German (Standard)"
+    GREEK 0x0408 "Greek"
+    ENGLISH_US 0x0409 "English (United States)"
+    FINNISH 0x040b "Finnish"
+    FRENCH 0x040c "French (Standard)"
+    HEBREW 0x040d "Hebrew"
+    HUNGARIAN 0x040e "Hungarian"
+    ITALIAN 0x0410 "Italian (Standard)"
+    JAPANESE 0x0411 "Japanese"
+    KOREAN 0x0412 "Korean"
+    DUTCH 0x0413 "Dutch (Netherlands)"
+    NORWEGIAN_BOKMAL 0x0414 "Norwegian (Bokmal)"
+    POLISH 0x0415 "Polish"
+    PORTUGUESE_BRAZIL 0x0416 "Portuguese (Brazil)"
+    ROMANIAN 0x0418 "Romanian"
+    RUSSIAN 0x0419 "Russian"
+    SWEDISH 0x041d "Swedish"
+    THAI 0x041e "Thai"
+    TURKISH 0x041f "Turkish"
+    UKRAINIAN 0x0422 "Ukrainian"
+    VIETNAMESE 0x042a "Vietnamese"
+    HINDI 0x0439 "Hindi"
+    HID_USAGE_DATA 0x04ff "HID (Usage Data Descriptor)"
+    CHINESE_PRC 0x0804 "Chinese (PRC)"
+    ENGLISH_UK 0x0809 "English (United Kingdom)"
+    PORTUGUESE 0x0816 "Portuguese (Standard)"
+    ENGLISH_AUSTRALIA 0x0c09 "English (Australian)"
+    SPANISH 0x0c0a "Spanish (Modern Sort)"
+    ENGLISH_CANADA 0x1009 "English (Canadian)"
+    FRENCH_CANADA 0x0c0c "French (Canadian)"
+}
+
+impl fmt::Debug for LanguageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(n) => write!(f, "{:#06x} ({})", self.0, n),
+            None => write!(f, "{:#06x}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn languages() {
+        let s = StringIter::from_raw(&[0x09, 0x04, 0x07, 0x04, 0x34, 0x12]).unwrap();
+        let mut l = s.languages();
+        assert_eq!(l.next(), Some(LanguageId::ENGLISH_US));
+        assert_eq!(l.next(), Some(LanguageId::GERMAN));
+        let unknown = l.next().unwrap();
+        assert_eq!(unknown.name(), None);
+        assert_eq!(l.next(), None);
+        assert_eq!(LanguageId::ENGLISH_UK.primary(), 0x09);
+        assert_eq!(LanguageId::ENGLISH_UK.sub(), 0x02);
+        assert_eq!(
+            LanguageId::ENGLISH_US.name(),
+            Some("English (United States)")
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let mut buf = [0; 8];
+        assert_eq!(encode_string("a𝄞", &mut buf), 8);
+        let s = StringIter::from_raw(&buf[2..]).unwrap();
+        assert!(s.chars().eq("a𝄞".chars()));
+        assert_eq!(encode_string("abcd", &mut buf), 8);
+        assert_eq!(buf[..2], [10, 3]);
+        let mut buf = [0; 255];
+        let long = [b'x'; 200];
+        assert_eq!(
+            encode_string(core::str::from_utf8(&long).unwrap(), &mut buf),
+            254
+        );
+        assert_eq!(buf[0], 254);
+        assert_eq!(encode_languages(&[LanguageId::ENGLISH_US], &mut buf), 4);
+        assert_eq!(buf[..4], [4, 3, 0x09, 0x04]);
+    }
+
+    #[test]
+    fn utf16() {
+        // "a€𝄞" followed by an unpaired high surrogate and "b".
+        let raw = [
+            0x61, 0, 0xac, 0x20, 0x34, 0xd8, 0x1e, 0xdd, 0x00, 0xd8, 0x62, 0,
+        ];
+        let s = StringIter::from_raw(&raw).unwrap();
+        assert!(s.chars().eq("a€𝄞\u{fffd}b".chars()));
+        struct W([u8; 16], usize);
+        impl fmt::Write for W {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0[self.1..][..s.len()].copy_from_slice(s.as_bytes());
+                self.1 += s.len();
+                Ok(())
+            }
+        }
+        let mut w = W([0; 16], 0);
+        fmt::Write::write_fmt(&mut w, format_args!("{}", s)).unwrap();
+        assert_eq!(&w.0[..w.1], "a€𝄞\u{fffd}b".as_bytes());
+        #[cfg(feature = "alloc")]
+        assert_eq!(s.to_string_lossy(), "a€𝄞\u{fffd}b");
+    }
+}
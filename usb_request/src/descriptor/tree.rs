@@ -0,0 +1,660 @@
+use super::{
+    decode, Configuration, Descriptor, Endpoint, EndpointTransfer, Interface, InterfaceAssociation,
+    InvalidDescriptor, Iter, SuperSpeedCompanion, SuperSpeedPlusIsochCompanion,
+};
+
+/// A view of a configuration and all interface, endpoint and class-specific descriptors that
+/// belong to it.
+///
+/// This is a zero-allocation view over the buffer returned by GET_DESCRIPTOR(Configuration).
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigurationTree<'a> {
+    configuration: Configuration,
+    buf: &'a [u8],
+}
+
+impl<'a> ConfigurationTree<'a> {
+    /// Parse a configuration descriptor with its `total_length` bytes of descriptors.
+    ///
+    /// Other speed configuration descriptors are accepted too.
+    ///
+    /// Any bytes beyond `total_length` are ignored.
+    pub fn new(buf: &'a [u8]) -> Result<Self, InvalidTree> {
+        let mut it = decode(buf);
+        let configuration = match it.next() {
+            Some(Ok(Descriptor::Configuration(c))) => c,
+            Some(Ok(Descriptor::OtherSpeedConfiguration(c))) => c,
+            Some(Ok(_)) | None => return Err(InvalidTree::NotConfiguration),
+            Some(Err(e)) => return Err(InvalidTree::Descriptor(e)),
+        };
+        let total_length = usize::from(configuration.total_length);
+        let buf = buf
+            .get(..total_length)
+            .ok_or(InvalidTree::Truncated {
+                total_length: configuration.total_length,
+            })?
+            .get(buf.len() - it.buf.len()..)
+            .ok_or(InvalidTree::Truncated {
+                total_length: configuration.total_length,
+            })?;
+        // Ensure all descriptors are valid so the iterators don't need to deal with errors.
+        decode(buf).try_for_each(|d| d.map(|_| ()).map_err(InvalidTree::Descriptor))?;
+        Ok(Self { configuration, buf })
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// All interfaces, including alternate settings.
+    pub fn interfaces(&self) -> InterfaceIter<'a> {
+        InterfaceIter {
+            it: decode(self.buf),
+        }
+    }
+
+    pub fn interface(&self, number: u8, alternate_setting: u8) -> Option<InterfaceView<'a>> {
+        self.interfaces().find(|i| {
+            i.interface.number == number && i.interface.alternate_setting == alternate_setting
+        })
+    }
+
+    pub fn alternate_settings(&self, number: u8) -> impl Iterator<Item = InterfaceView<'a>> {
+        self.interfaces()
+            .filter(move |i| i.interface.number == number)
+    }
+
+    /// Split the configuration into functions.
+    ///
+    /// Interfaces grouped by an interface association descriptor form a single function.
+    /// Every other interface is a function by itself.
+    pub fn functions(&self) -> FunctionIter<'a> {
+        FunctionIter {
+            tree: *self,
+            it: decode(self.buf),
+            seen: [0; 8],
+        }
+    }
+
+    /// Report how `other` differs from this configuration.
+    ///
+    /// This is intended to compare a configuration with the other speed configuration
+    /// of the same index. `total_length` is not compared.
+    pub fn compare(&self, other: &ConfigurationTree<'a>, mut f: impl FnMut(Difference<'a>)) {
+        let (c, o) = (self.configuration, other.configuration);
+        if c != (Configuration {
+            total_length: c.total_length,
+            ..o
+        }) {
+            f(Difference::Configuration {
+                current: c,
+                other: o,
+            });
+        }
+        for ci in self.interfaces() {
+            let (n, alt) = (ci.interface.number, ci.interface.alternate_setting);
+            let Some(oi) = other.interface(n, alt) else {
+                f(Difference::Interface {
+                    current: Some(ci),
+                    other: None,
+                });
+                continue;
+            };
+            if ci.interface != oi.interface {
+                f(Difference::Interface {
+                    current: Some(ci),
+                    other: Some(oi),
+                });
+            }
+            let mut endpoint = |current, other| {
+                f(Difference::Endpoint {
+                    interface: n,
+                    alternate_setting: alt,
+                    current,
+                    other,
+                })
+            };
+            for ce in ci.endpoints() {
+                let oe = oi
+                    .endpoints()
+                    .find(|e| e.endpoint.address == ce.endpoint.address);
+                match oe {
+                    Some(oe) if oe.endpoint == ce.endpoint => {}
+                    oe => endpoint(Some(ce.endpoint), oe.map(|e| e.endpoint)),
+                }
+            }
+            for oe in oi.endpoints() {
+                if !ci
+                    .endpoints()
+                    .any(|e| e.endpoint.address == oe.endpoint.address)
+                {
+                    endpoint(None, Some(oe.endpoint));
+                }
+            }
+        }
+        for oi in other.interfaces() {
+            let (n, alt) = (oi.interface.number, oi.interface.alternate_setting);
+            if self.interface(n, alt).is_none() {
+                f(Difference::Interface {
+                    current: None,
+                    other: Some(oi),
+                });
+            }
+        }
+    }
+
+    /// Descriptors between the configuration descriptor and the first interface descriptor.
+    pub fn class_descriptors(&self) -> Iter<'a> {
+        decode(until(self.buf, is_interface).0)
+    }
+
+    /// Check whether `num_interfaces` and `num_endpoints` match the descriptors that are
+    /// present.
+    pub fn check(&self) -> Result<(), CountMismatch> {
+        let mut seen = [0u32; 8];
+        for i in self.interfaces() {
+            let n = usize::from(i.interface.number);
+            seen[n / 32] |= 1 << (n % 32);
+            let found = i.endpoints().count();
+            if found != usize::from(i.interface.num_endpoints) {
+                return Err(CountMismatch::Endpoints {
+                    interface: i.interface.number,
+                    alternate_setting: i.interface.alternate_setting,
+                    expected: i.interface.num_endpoints,
+                    found,
+                });
+            }
+        }
+        let found = seen.iter().map(|n| n.count_ones() as usize).sum();
+        if found != usize::from(self.configuration.num_interfaces) {
+            return Err(CountMismatch::Interfaces {
+                expected: self.configuration.num_interfaces,
+                found,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A group of interfaces that are handled by a single driver.
+#[derive(Clone, Copy, Debug)]
+pub struct Function<'a> {
+    association: Option<InterfaceAssociation>,
+    first_interface: u8,
+    tree: ConfigurationTree<'a>,
+}
+
+impl<'a> Function<'a> {
+    pub fn association(&self) -> Option<&InterfaceAssociation> {
+        self.association.as_ref()
+    }
+
+    /// All interfaces of this function, including alternate settings.
+    pub fn interfaces(&self) -> impl Iterator<Item = InterfaceView<'a>> {
+        let f = *self;
+        self.tree
+            .interfaces()
+            .filter(move |i| f.contains(i.interface.number))
+    }
+
+    fn contains(&self, interface: u8) -> bool {
+        self.association
+            .map_or(interface == self.first_interface, |a| a.contains(interface))
+    }
+}
+
+/// An interface alternate setting and the descriptors that belong to it.
+#[derive(Clone, Copy, Debug)]
+pub struct InterfaceView<'a> {
+    interface: Interface,
+    buf: &'a [u8],
+}
+
+impl<'a> InterfaceView<'a> {
+    pub fn interface(&self) -> &Interface {
+        &self.interface
+    }
+
+    pub fn endpoints(&self) -> EndpointIter<'a> {
+        EndpointIter {
+            it: decode(self.buf),
+        }
+    }
+
+    /// Class-specific descriptors between the interface descriptor and the first endpoint
+    /// descriptor, e.g. a HID descriptor.
+    pub fn class_descriptors(&self) -> Iter<'a> {
+        decode(until(self.buf, is_endpoint).0)
+    }
+}
+
+/// An endpoint and the descriptors that belong to it.
+#[derive(Clone, Copy, Debug)]
+pub struct EndpointView<'a> {
+    endpoint: Endpoint,
+    buf: &'a [u8],
+}
+
+impl<'a> EndpointView<'a> {
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Descriptors following the endpoint descriptor, e.g. class-specific endpoint
+    /// descriptors.
+    pub fn class_descriptors(&self) -> Iter<'a> {
+        decode(self.buf)
+    }
+
+    /// The SuperSpeed Endpoint Companion of this endpoint.
+    ///
+    /// This is only present for SuperSpeed devices.
+    pub fn companion(&self) -> Option<SuperSpeedCompanion> {
+        self.class_descriptors().find_map(|d| match d {
+            Ok(Descriptor::SuperSpeedCompanion(c)) => Some(c),
+            _ => None,
+        })
+    }
+
+    /// The SuperSpeedPlus Isochronous Endpoint Companion of this endpoint.
+    pub fn isoch_companion(&self) -> Option<SuperSpeedPlusIsochCompanion> {
+        self.class_descriptors().find_map(|d| match d {
+            Ok(Descriptor::SuperSpeedPlusIsochCompanion(c)) => Some(c),
+            _ => None,
+        })
+    }
+
+    /// The maximum amount of streams of a bulk endpoint, or 0 if streams are not supported.
+    pub fn max_streams(&self) -> u32 {
+        match self.endpoint.attributes.transfer() {
+            EndpointTransfer::Bulk => self.companion().map_or(0, |c| c.max_streams()),
+            _ => 0,
+        }
+    }
+
+    /// The amount of bytes transferred every service interval of a periodic endpoint.
+    ///
+    /// Returns `None` for control and bulk endpoints.
+    pub fn bytes_per_interval(&self) -> Option<u32> {
+        match self.endpoint.attributes.transfer() {
+            EndpointTransfer::Control | EndpointTransfer::Bulk => return None,
+            EndpointTransfer::Isoch | EndpointTransfer::Interrupt => {}
+        }
+        if let Some(c) = self.isoch_companion() {
+            return Some(c.bytes_per_interval);
+        }
+        if let Some(c) = self.companion() {
+            return Some(c.bytes_per_interval.into());
+        }
+        Some(self.endpoint.max_packet_size.bytes_per_interval())
+    }
+}
+
+#[derive(Clone)]
+pub struct InterfaceIter<'a> {
+    it: Iter<'a>,
+}
+
+impl<'a> Iterator for InterfaceIter<'a> {
+    type Item = InterfaceView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Descriptor::Interface(interface) = self.it.next()?.ok()? {
+                let (buf, rem) = until(self.it.buf, is_interface);
+                self.it.buf = rem;
+                return Some(InterfaceView { interface, buf });
+            }
+        }
+    }
+}
+
+pub struct FunctionIter<'a> {
+    tree: ConfigurationTree<'a>,
+    it: Iter<'a>,
+    seen: [u32; 8],
+}
+
+impl FunctionIter<'_> {
+    fn mark(&mut self, interface: u8) -> bool {
+        let n = usize::from(interface);
+        let (w, b) = (&mut self.seen[n / 32], 1 << (n % 32));
+        let new = *w & b == 0;
+        *w |= b;
+        new
+    }
+}
+
+impl<'a> Iterator for FunctionIter<'a> {
+    type Item = Function<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let f = match self.it.next()?.ok()? {
+                Descriptor::InterfaceAssociation(a) => {
+                    (0..a.interface_count).for_each(|i| {
+                        self.mark(a.first_interface.wrapping_add(i));
+                    });
+                    Function {
+                        association: Some(a),
+                        first_interface: a.first_interface,
+                        tree: self.tree,
+                    }
+                }
+                Descriptor::Interface(i) if self.mark(i.number) => Function {
+                    association: None,
+                    first_interface: i.number,
+                    tree: self.tree,
+                },
+                _ => continue,
+            };
+            return Some(f);
+        }
+    }
+}
+
+pub struct EndpointIter<'a> {
+    it: Iter<'a>,
+}
+
+impl<'a> Iterator for EndpointIter<'a> {
+    type Item = EndpointView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Descriptor::Endpoint(endpoint) = self.it.next()?.ok()? {
+                let (buf, rem) = until(self.it.buf, is_endpoint);
+                self.it.buf = rem;
+                return Some(EndpointView { endpoint, buf });
+            }
+        }
+    }
+}
+
+fn is_interface(d: &Descriptor) -> bool {
+    matches!(
+        d,
+        Descriptor::Interface(_) | Descriptor::InterfaceAssociation(_)
+    )
+}
+
+fn is_endpoint(d: &Descriptor) -> bool {
+    is_interface(d) || matches!(d, Descriptor::Endpoint(_))
+}
+
+/// Split `buf` before the first descriptor for which `f` returns `true`.
+fn until(buf: &[u8], f: fn(&Descriptor) -> bool) -> (&[u8], &[u8]) {
+    let mut it = decode(buf);
+    loop {
+        let rem = it.buf;
+        match it.next() {
+            Some(Ok(d)) if !f(&d) => {}
+            _ => return buf.split_at(buf.len() - rem.len()),
+        }
+    }
+}
+
+/// A difference between two configurations, as reported by [`ConfigurationTree::compare`].
+#[derive(Debug)]
+pub enum Difference<'a> {
+    /// The attributes, power consumption or amount of interfaces differ.
+    Configuration {
+        current: Configuration,
+        other: Configuration,
+    },
+    /// An interface alternate setting is only present in one configuration or its
+    /// descriptor differs.
+    Interface {
+        current: Option<InterfaceView<'a>>,
+        other: Option<InterfaceView<'a>>,
+    },
+    /// An endpoint is only present in one configuration or its descriptor differs.
+    Endpoint {
+        interface: u8,
+        alternate_setting: u8,
+        current: Option<Endpoint>,
+        other: Option<Endpoint>,
+    },
+}
+
+#[derive(Debug)]
+pub enum InvalidTree {
+    /// The first descriptor is not a (other speed) configuration descriptor.
+    NotConfiguration,
+    /// The buffer is shorter than `total_length`.
+    Truncated {
+        total_length: u16,
+    },
+    Descriptor(InvalidDescriptor),
+}
+
+#[derive(Debug)]
+pub enum CountMismatch {
+    Interfaces {
+        expected: u8,
+        found: usize,
+    },
+    Endpoints {
+        interface: u8,
+        alternate_setting: u8,
+        expected: u8,
+        found: usize,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Interface 0 is a HID interface, interface 1 has a zero-bandwidth alternate setting.
+    const COMPOSITE: &[u8] = &[
+        0x09, 0x02, 0x46, 0x00, 0x02, 0x01, 0x00, 0x80, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00, // interface 0
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x20, 0x00, // hid
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint 0x81
+        0x09, 0x04, 0x01, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, // interface 1
+        0x09, 0x04, 0x01, 0x01, 0x02, 0xff, 0x00, 0x00, 0x00, // interface 1, alt 1
+        0x07, 0x05, 0x02, 0x01, 0xc0, 0x00, 0x01, // endpoint 0x02
+        0x04, 0xff, 0x12, 0x34, // vendor-specific
+        0x07, 0x05, 0x83, 0x01, 0x04, 0x00, 0x01, // endpoint 0x83
+        0x08, 0xff, 0, 0, 0, 0, 0, 0, // trailing, beyond total_length
+    ];
+
+    #[test]
+    fn composite() {
+        let t = ConfigurationTree::new(COMPOSITE).unwrap();
+        assert_eq!(t.configuration().num_interfaces, 2);
+        assert_eq!(t.interfaces().count(), 3);
+        assert_eq!(t.alternate_settings(1).count(), 2);
+        t.check().unwrap();
+
+        let i = t.interface(0, 0).unwrap();
+        assert!(matches!(
+            i.class_descriptors().next(),
+            Some(Ok(Descriptor::Hid(_)))
+        ));
+        assert_eq!(i.class_descriptors().count(), 1);
+
+        let i = t.interface(1, 1).unwrap();
+        let mut e = i.endpoints();
+        let e0 = e.next().unwrap();
+        assert_eq!(e0.endpoint().max_packet_size.size(), 192);
+        assert!(matches!(
+            e0.class_descriptors().next(),
+            Some(Ok(Descriptor::Unknown {
+                ty: 0xff,
+                data: &[0x12, 0x34]
+            }))
+        ));
+        let e1 = e.next().unwrap();
+        assert_eq!(e1.endpoint().max_packet_size.size(), 4);
+        assert_eq!(e1.class_descriptors().count(), 0);
+        assert!(e.next().is_none());
+
+        assert!(t.interface(1, 2).is_none());
+    }
+
+    #[test]
+    fn count_mismatch() {
+        let mut buf = [0; 0x46];
+        buf.copy_from_slice(&COMPOSITE[..0x46]);
+        buf[4] = 3;
+        let t = ConfigurationTree::new(&buf).unwrap();
+        assert!(matches!(
+            t.check(),
+            Err(CountMismatch::Interfaces {
+                expected: 3,
+                found: 2
+            })
+        ));
+        buf[4] = 2;
+        buf[9 + 4] = 2;
+        let t = ConfigurationTree::new(&buf).unwrap();
+        assert!(matches!(
+            t.check(),
+            Err(CountMismatch::Endpoints {
+                interface: 0,
+                alternate_setting: 0,
+                expected: 2,
+                found: 1,
+            })
+        ));
+    }
+
+    // CDC-ACM grouped with an IAD, followed by a HID interface.
+    const CDC_HID: &[u8] = &[
+        0x09, 0x02, 0x64, 0x00, 0x03, 0x01, 0x00, 0x80, 0x32, // configuration
+        0x08, 0x0b, 0x00, 0x02, 0x02, 0x02, 0x01, 0x00, // interface association
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x02, 0x02, 0x01, 0x00, // interface 0
+        0x05, 0x24, 0x00, 0x10, 0x01, // cdc header
+        0x05, 0x24, 0x01, 0x00, 0x01, // cdc call management
+        0x04, 0x24, 0x02, 0x02, // cdc acm
+        0x05, 0x24, 0x06, 0x00, 0x01, // cdc union
+        0x07, 0x05, 0x82, 0x03, 0x08, 0x00, 0x10, // endpoint 0x82
+        0x09, 0x04, 0x01, 0x00, 0x02, 0x0a, 0x00, 0x00, 0x00, // interface 1
+        0x07, 0x05, 0x01, 0x02, 0x40, 0x00, 0x00, // endpoint 0x01
+        0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00, // endpoint 0x81
+        0x09, 0x04, 0x02, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface 2
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // hid
+        0x07, 0x05, 0x83, 0x03, 0x08, 0x00, 0x0a, // endpoint 0x83
+    ];
+
+    #[test]
+    fn functions() {
+        let t = ConfigurationTree::new(CDC_HID).unwrap();
+        t.check().unwrap();
+        let mut f = t.functions();
+
+        let cdc = f.next().unwrap();
+        let a = cdc.association().unwrap();
+        assert_eq!((a.first_interface, a.interface_count), (0, 2));
+        assert_eq!(a.function_class, 0x02);
+        let mut i = cdc.interfaces();
+        let i0 = i.next().unwrap();
+        assert_eq!(i0.interface().number, 0);
+        assert_eq!(i0.class_descriptors().count(), 4);
+        assert_eq!(i0.endpoints().count(), 1);
+        assert_eq!(i.next().unwrap().interface().number, 1);
+        assert!(i.next().is_none());
+
+        let hid = f.next().unwrap();
+        assert!(hid.association().is_none());
+        let mut i = hid.interfaces();
+        assert_eq!(i.next().unwrap().interface().number, 2);
+        assert!(i.next().is_none());
+
+        assert!(f.next().is_none());
+    }
+
+    // UAS-like interface with streams on a bulk endpoint and a high-bandwidth isochronous
+    // endpoint, as found on a SuperSpeedPlus device.
+    const SUPERSPEED: &[u8] = &[
+        0x09, 0x02, 0x3b, 0x00, 0x01, 0x01, 0x00, 0x80, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x03, 0xff, 0x00, 0x00, 0x00, // interface 0
+        0x07, 0x05, 0x81, 0x02, 0x00, 0x04, 0x00, // endpoint 0x81
+        0x06, 0x30, 0x0f, 0x05, 0x00, 0x00, // companion, 32 streams
+        0x07, 0x05, 0x02, 0x01, 0x00, 0x04, 0x01, // endpoint 0x02
+        0x06, 0x30, 0x0f, 0x80, 0x00, 0x00, // companion
+        0x08, 0x31, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, // isoch companion, 64K
+        0x07, 0x05, 0x83, 0x03, 0x00, 0x04, 0x01, // endpoint 0x83, no companion
+    ];
+
+    #[test]
+    fn companions() {
+        let t = ConfigurationTree::new(SUPERSPEED).unwrap();
+        t.check().unwrap();
+        let mut e = t.interface(0, 0).unwrap().endpoints();
+        let bulk = e.next().unwrap();
+        assert_eq!(bulk.companion().unwrap().max_burst, 15);
+        assert_eq!(bulk.max_streams(), 32);
+        assert_eq!(bulk.bytes_per_interval(), None);
+        let isoch = e.next().unwrap();
+        assert!(isoch.companion().unwrap().ssp_isoch_companion());
+        assert_eq!(isoch.max_streams(), 0);
+        assert_eq!(isoch.bytes_per_interval(), Some(0x10000));
+        let int = e.next().unwrap();
+        assert!(int.companion().is_none());
+        assert_eq!(int.bytes_per_interval(), Some(1024));
+    }
+
+    #[test]
+    fn other_speed() {
+        // High-speed bulk endpoints with a high-bandwidth interrupt endpoint that doesn't exist
+        // at full speed.
+        const HIGH_SPEED: &[u8] = &[
+            0x09, 0x02, 0x27, 0x00, 0x01, 0x01, 0x00, 0x80, 0xfa, // configuration
+            0x09, 0x04, 0x00, 0x00, 0x03, 0xff, 0x00, 0x00, 0x00, // interface 0
+            0x07, 0x05, 0x81, 0x02, 0x00, 0x02, 0x00, // endpoint 0x81
+            0x07, 0x05, 0x02, 0x02, 0x00, 0x02, 0x00, // endpoint 0x02
+            0x07, 0x05, 0x83, 0x03, 0x00, 0x0c, 0x01, // endpoint 0x83
+        ];
+        const FULL_SPEED: &[u8] = &[
+            0x09, 0x07, 0x20, 0x00, 0x01, 0x01, 0x00, 0x80, 0xfa, // other speed configuration
+            0x09, 0x04, 0x00, 0x00, 0x02, 0xff, 0x00, 0x00, 0x00, // interface 0
+            0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00, // endpoint 0x81
+            0x07, 0x05, 0x02, 0x02, 0x00, 0x02, 0x00, // endpoint 0x02
+        ];
+        let hs = ConfigurationTree::new(HIGH_SPEED).unwrap();
+        let fs = ConfigurationTree::new(FULL_SPEED).unwrap();
+        let mut n = 0;
+        hs.compare(&fs, |d| {
+            match (n, d) {
+                (0, Difference::Interface { current, other }) => {
+                    assert_eq!(current.unwrap().interface().num_endpoints, 3);
+                    assert_eq!(other.unwrap().interface().num_endpoints, 2);
+                }
+                (
+                    1,
+                    Difference::Endpoint {
+                        current: Some(c),
+                        other: Some(o),
+                        ..
+                    },
+                ) => assert_eq!(
+                    (c.max_packet_size.size(), o.max_packet_size.size()),
+                    (512, 64)
+                ),
+                (
+                    2,
+                    Difference::Endpoint {
+                        current: Some(c),
+                        other: None,
+                        ..
+                    },
+                ) => assert_eq!(u8::from(c.address), 0x83),
+                (_, d) => panic!("{:?}", d),
+            }
+            n += 1;
+        });
+        assert_eq!(n, 3);
+        fs.compare(&fs, |d| panic!("{:?}", d));
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(matches!(
+            ConfigurationTree::new(&COMPOSITE[..0x40]),
+            Err(InvalidTree::Truncated { total_length: 0x46 })
+        ));
+    }
+}
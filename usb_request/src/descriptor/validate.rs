@@ -0,0 +1,317 @@
+use super::{decode, Descriptor, EndpointAddress, InvalidDescriptor, MaxPacketSize};
+use crate::Speed;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The descriptors violate the specification but can be used.
+    Warning,
+    /// The descriptors can't be used as they are.
+    Error,
+}
+
+/// A problem found by [`validate`].
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    pub severity: Severity,
+    /// Offset of the offending descriptor in the buffer.
+    pub offset: usize,
+    /// The offending descriptor.
+    pub descriptor: &'a [u8],
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Debug)]
+pub enum DiagnosticKind {
+    /// The first descriptor is not a (other speed) configuration descriptor.
+    NotConfiguration,
+    /// A descriptor could not be parsed. Validation stops if the length is invalid.
+    Malformed(InvalidDescriptor),
+    TotalLength {
+        total_length: u16,
+        found: usize,
+    },
+    NumInterfaces {
+        num_interfaces: u8,
+        found: usize,
+    },
+    NumEndpoints {
+        num_endpoints: u8,
+        found: usize,
+    },
+    /// An interface with the same number and alternate setting was already described, or an
+    /// alternate setting does not directly follow the other settings of its interface.
+    DuplicateInterface,
+    /// An endpoint with the same address exists in the same alternate setting or in another
+    /// interface.
+    DuplicateEndpoint(EndpointAddress),
+    /// An endpoint that does not belong to an interface.
+    EndpointOutsideInterface,
+    MaxPacketSize(MaxPacketSize),
+    Interval(u8),
+    /// The configuration draws more than the bus can supply.
+    MaxPower {
+        milliamps: u16,
+    },
+}
+
+/// Check a configuration descriptor with all its other descriptors against the specification.
+///
+/// Every problem is reported to `f`.
+pub fn validate<'a>(buf: &'a [u8], speed: Speed, mut f: impl FnMut(Diagnostic<'a>)) {
+    let at = |offset: usize| &buf[offset..][..usize::from(buf[offset]).min(buf.len() - offset)];
+    let mut report = |severity, offset, kind| {
+        f(Diagnostic {
+            severity,
+            offset,
+            descriptor: at(offset),
+            kind,
+        })
+    };
+    let c = match decode(buf).next() {
+        Some(Ok(Descriptor::Configuration(c) | Descriptor::OtherSpeedConfiguration(c))) => c,
+        Some(Err(e)) => return report(Severity::Error, 0, DiagnosticKind::Malformed(e)),
+        Some(Ok(_)) => return report(Severity::Error, 0, DiagnosticKind::NotConfiguration),
+        None => return,
+    };
+    if usize::from(c.total_length) != buf.len() {
+        let severity = match usize::from(c.total_length) > buf.len() {
+            true => Severity::Error,
+            false => Severity::Warning,
+        };
+        report(
+            severity,
+            0,
+            DiagnosticKind::TotalLength {
+                total_length: c.total_length,
+                found: buf.len(),
+            },
+        );
+    }
+    let milliamps = match speed {
+        Speed::Super | Speed::SuperPlus => u16::from(c.max_power) * 8,
+        _ => u16::from(c.max_power) * 2,
+    };
+    if milliamps > if speed >= Speed::Super { 900 } else { 500 } {
+        report(Severity::Warning, 0, DiagnosticKind::MaxPower { milliamps });
+    }
+
+    let end = buf.len().min(c.total_length.into());
+    // Interface numbers that were seen.
+    let mut interfaces = [0u32; 8];
+    // Offset and num_endpoints of the current interface, the endpoints found and their addresses.
+    let mut interface = None::<(usize, u8, usize, u32)>;
+    let mut number = 0;
+    // Alternate settings of the current interface number.
+    let mut alternates = [0u32; 8];
+    // Endpoints of each interface number in any of its alternate settings, and of all interfaces.
+    let mut interface_endpoints = [0u32; 256];
+    let mut endpoints_used = 0u32;
+    let mut offset = usize::from(buf[0]);
+    while offset < end {
+        let d = match decode(&buf[offset..end]).next().unwrap() {
+            Ok(d) => d,
+            Err(e) => {
+                let stop = matches!(e, InvalidDescriptor::Truncated { .. });
+                report(Severity::Error, offset, DiagnosticKind::Malformed(e));
+                if stop {
+                    break;
+                }
+                offset += usize::from(buf[offset]);
+                continue;
+            }
+        };
+        match d {
+            Descriptor::Interface(i) => {
+                close(&mut report, interface);
+                let n = usize::from(i.number);
+                let seen = interfaces[n / 32] & 1 << (n % 32) != 0;
+                let alt = usize::from(i.alternate_setting);
+                let duplicate = if i.number == number && seen {
+                    alternates[alt / 32] & 1 << (alt % 32) != 0
+                } else {
+                    alternates = [0; 8];
+                    // The interface was left, so its settings are no longer tracked.
+                    seen
+                };
+                if duplicate {
+                    report(Severity::Error, offset, DiagnosticKind::DuplicateInterface);
+                }
+                alternates[alt / 32] |= 1 << (alt % 32);
+                number = i.number;
+                interfaces[n / 32] |= 1 << (n % 32);
+                interface = Some((offset, i.num_endpoints, 0, 0));
+            }
+            Descriptor::Endpoint(e) => {
+                let Some((_, _, found, endpoints)) = &mut interface else {
+                    report(
+                        Severity::Error,
+                        offset,
+                        DiagnosticKind::EndpointOutsideInterface,
+                    );
+                    offset += usize::from(buf[offset]);
+                    continue;
+                };
+                *found += 1;
+                let bit = e.address.bit();
+                // Alternate settings of an interface may reuse its endpoints, but other
+                // interfaces may not.
+                let own = &mut interface_endpoints[usize::from(number)];
+                let other = endpoints_used & !*own;
+                if (*endpoints | other) & bit != 0 {
+                    let kind = DiagnosticKind::DuplicateEndpoint(e.address);
+                    report(Severity::Error, offset, kind);
+                }
+                *endpoints |= bit;
+                *own |= bit;
+                endpoints_used |= bit;
+                let transfer = e.attributes.transfer();
+                if !speed.max_packet_size_valid(transfer, e.max_packet_size) {
+                    let kind = DiagnosticKind::MaxPacketSize(e.max_packet_size);
+                    report(Severity::Error, offset, kind);
+                }
+                if !speed.interval_valid(transfer, e.interval) {
+                    let kind = DiagnosticKind::Interval(e.interval);
+                    report(Severity::Warning, offset, kind);
+                }
+            }
+            _ => {}
+        }
+        offset += usize::from(buf[offset]);
+    }
+    close(&mut report, interface);
+    let found = interfaces.iter().map(|n| n.count_ones() as usize).sum();
+    if found != usize::from(c.num_interfaces) {
+        let kind = DiagnosticKind::NumInterfaces {
+            num_interfaces: c.num_interfaces,
+            found,
+        };
+        report(Severity::Error, 0, kind);
+    }
+}
+
+/// Report a mismatch of `num_endpoints` once all endpoints of an interface were seen.
+fn close(
+    report: &mut impl FnMut(Severity, usize, DiagnosticKind),
+    interface: Option<(usize, u8, usize, u32)>,
+) {
+    if let Some((offset, num_endpoints, found, _)) = interface {
+        if usize::from(num_endpoints) != found {
+            let kind = DiagnosticKind::NumEndpoints {
+                num_endpoints,
+                found,
+            };
+            report(Severity::Error, offset, kind);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        const BROKEN: &[u8] = &[
+            0x09, 0x02, 0x32, 0x00, 0x02, 0x01, 0x00, 0x80, 0xfa, // configuration
+            0x07, 0x05, 0x82, 0x02, 0x40, 0x00, 0x00, // endpoint without interface
+            0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface
+            0x07, 0x05, 0x81, 0x03, 0x41, 0x00, 0x00, // endpoint
+            0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00, // endpoint
+            0x09, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, // interface
+        ];
+        let mut d = [(Severity::Warning, 0, &[][..]); 16];
+        let mut n = 0;
+        super::validate(BROKEN, Speed::Full, |diag| {
+            d[n] = (diag.severity, diag.offset, diag.descriptor);
+            n += 1;
+            let kind = match diag.kind {
+                DiagnosticKind::TotalLength {
+                    total_length: 0x32,
+                    found: 0x30,
+                } => 0,
+                DiagnosticKind::EndpointOutsideInterface => 1,
+                DiagnosticKind::MaxPacketSize(m) if m.size() == 0x41 => 2,
+                DiagnosticKind::Interval(0) => 3,
+                DiagnosticKind::DuplicateEndpoint(_) => 4,
+                DiagnosticKind::NumEndpoints {
+                    num_endpoints: 1,
+                    found: 2,
+                } => 5,
+                DiagnosticKind::DuplicateInterface => 6,
+                DiagnosticKind::NumInterfaces {
+                    num_interfaces: 2,
+                    found: 1,
+                } => 7,
+                k => panic!("{:?}", k),
+            };
+            assert_eq!(kind, n - 1);
+        });
+        assert_eq!(n, 8);
+        assert_eq!(d[0], (Severity::Error, 0, &BROKEN[..9]));
+        assert_eq!(d[1], (Severity::Error, 9, &BROKEN[9..16]));
+        assert_eq!(d[3], (Severity::Warning, 25, &BROKEN[25..32]));
+        assert_eq!(d[5], (Severity::Error, 16, &BROKEN[16..25]));
+
+        let mut ok = true;
+        let mut c = [0x09, 0x02, 0x09, 0x00, 0x00, 0x01, 0x00, 0x80, 0x70];
+        super::validate(&c, Speed::Super, |_| ok = false);
+        assert!(ok);
+        c[8] = 0xfb;
+        super::validate(&c, Speed::High, |d| {
+            assert!(matches!(
+                d.kind,
+                DiagnosticKind::MaxPower { milliamps: 502 }
+            ))
+        });
+    }
+
+    #[test]
+    fn duplicates() {
+        const C: &[u8] = &[
+            0x09, 0x02, 0x39, 0x00, 0x02, 0x01, 0x00, 0x80, 0xfa, // configuration
+            0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface 0
+            0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00, // endpoint
+            0x09, 0x04, 0x00, 0x01, 0x01, 0xff, 0x00, 0x00, 0x00, // alternate setting 1
+            0x07, 0x05, 0x81, 0x03, 0x40, 0x00, 0x01, // same endpoint
+            0x09, 0x04, 0x01, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface 1
+            0x07, 0x05, 0x81, 0x02, 0x40, 0x00, 0x00, // endpoint of interface 0
+        ];
+        let mut n = 0;
+        super::validate(C, Speed::Full, |d| {
+            n += 1;
+            assert_eq!(d.offset, 0x32);
+            assert!(matches!(d.kind, DiagnosticKind::DuplicateEndpoint(_)));
+        });
+        assert_eq!(n, 1);
+
+        // All alternate settings are tracked.
+        let mut c = [0; 9 * 101];
+        c[..9].copy_from_slice(&[0x09, 0x02, 0x8d, 0x03, 0x01, 0x01, 0x00, 0x80, 0xfa]);
+        for (i, d) in c[9..].chunks_mut(9).enumerate() {
+            d.copy_from_slice(&[0x09, 0x04, 0x00, i as u8, 0x00, 0xff, 0x00, 0x00, 0x00]);
+        }
+        c[9 * 100 + 3] = 70;
+        let mut n = 0;
+        super::validate(&c, Speed::Full, |d| {
+            n += 1;
+            assert_eq!(d.offset, 9 * 100);
+            assert!(matches!(d.kind, DiagnosticKind::DuplicateInterface));
+        });
+        assert_eq!(n, 1);
+
+        // Alternate settings must not return to an interface that was left.
+        const D: &[u8] = &[
+            0x09, 0x02, 0x24, 0x00, 0x02, 0x01, 0x00, 0x80, 0xfa, // configuration
+            0x09, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, // interface 0
+            0x09, 0x04, 0x01, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, // interface 1
+            0x09, 0x04, 0x00, 0x01, 0x00, 0xff, 0x00, 0x00, 0x00, // alternate setting 1
+        ];
+        let mut n = 0;
+        super::validate(D, Speed::Full, |d| {
+            n += 1;
+            assert_eq!(d.offset, 0x1b);
+            assert!(matches!(d.kind, DiagnosticKind::DuplicateInterface));
+        });
+        assert_eq!(n, 1);
+    }
+}
@@ -0,0 +1,515 @@
+//! Device-side handling of standard requests.
+
+use crate::{
+    descriptor::{
+        encode_languages, encode_string, ConfigurationTree, Device, DeviceQualifier,
+        EndpointAddress, EndpointNumber, GetDescriptor, InvalidTree, LanguageId,
+    },
+    Feature, RawRequest, Recipient, Request,
+};
+
+/// The descriptors of a device.
+#[derive(Clone, Copy, Debug)]
+pub struct Descriptors<'a> {
+    pub device: Device,
+    /// Each configuration with all its interface, endpoint and class-specific descriptors.
+    pub configurations: &'a [&'a [u8]],
+    /// The configurations at the other speed, only for high-speed capable devices.
+    pub other_speed_configurations: &'a [&'a [u8]],
+    pub device_qualifier: Option<DeviceQualifier>,
+    pub bos: Option<&'a [u8]>,
+    pub languages: &'a [LanguageId],
+    /// The strings at index 1 and up.
+    ///
+    /// The same strings are returned for every language.
+    pub strings: &'a [&'a str],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceState {
+    Default,
+    Address(u8),
+    Configured { address: u8, configuration: u8 },
+}
+
+/// How to finish a control transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Response {
+    /// Send the first `n` bytes of the buffer in the data stage.
+    In(usize),
+    /// Acknowledge in the status stage.
+    Ack,
+    /// Acknowledge, then switch to the new address.
+    AckSetAddress(u8),
+}
+
+/// Handles class and vendor requests and is notified of state changes.
+///
+/// Returning `None` stalls the request.
+pub trait ClassHandler {
+    /// A class, vendor or unknown standard request.
+    ///
+    /// For OUT requests `data` holds the data stage, for IN requests the response is written to
+    /// it.
+    fn request(&mut self, request: RawRequest, data: &mut [u8]) -> Option<Response>;
+
+    /// A configuration was selected, 0 if the device was deconfigured.
+    fn configured(&mut self, _configuration: u8) {}
+
+    /// An alternate setting was selected.
+    fn interface(&mut self, _interface: u8, _alternate_setting: u8) {}
+
+    /// An endpoint was halted or resumed.
+    fn halt(&mut self, _endpoint: EndpointAddress, _halted: bool) {}
+}
+
+/// Stalls all class and vendor requests.
+impl ClassHandler for () {
+    fn request(&mut self, _: RawRequest, _: &mut [u8]) -> Option<Response> {
+        None
+    }
+}
+
+/// Answers standard requests from a set of descriptors.
+///
+/// Interface requests for interface numbers above 31 are stalled.
+pub struct DeviceHandler<'a> {
+    descriptors: Descriptors<'a>,
+    state: DeviceState,
+    self_powered: bool,
+    remote_wakeup: bool,
+    alternate_settings: [u8; 32],
+    halted: u32,
+}
+
+impl<'a> DeviceHandler<'a> {
+    pub fn new(descriptors: Descriptors<'a>) -> Result<Self, InvalidTree> {
+        for c in descriptors
+            .configurations
+            .iter()
+            .chain(descriptors.other_speed_configurations)
+        {
+            ConfigurationTree::new(c)?;
+        }
+        Ok(Self {
+            descriptors,
+            state: DeviceState::Default,
+            self_powered: false,
+            remote_wakeup: false,
+            alternate_settings: [0; 32],
+            halted: 0,
+        })
+    }
+
+    pub fn state(&self) -> DeviceState {
+        self.state
+    }
+
+    /// Whether the host enabled remote wakeup.
+    pub fn remote_wakeup(&self) -> bool {
+        self.remote_wakeup
+    }
+
+    /// Set the self-powered bit reported by GET_STATUS.
+    pub fn set_self_powered(&mut self, self_powered: bool) {
+        self.self_powered = self_powered;
+    }
+
+    /// Whether `endpoint` is halted.
+    pub fn halted(&self, endpoint: EndpointAddress) -> bool {
+        self.halted & endpoint.bit() != 0
+    }
+
+    /// The device was reset by the host.
+    pub fn reset(&mut self) {
+        self.state = DeviceState::Default;
+        self.remote_wakeup = false;
+        self.alternate_settings = [0; 32];
+        self.halted = 0;
+    }
+
+    /// The active configuration.
+    pub fn configuration(&self) -> Option<ConfigurationTree<'a>> {
+        match self.state {
+            DeviceState::Configured { configuration, .. } => self.find(configuration),
+            _ => None,
+        }
+    }
+
+    /// Handle a SETUP packet.
+    ///
+    /// `data` holds the data stage of OUT requests and receives the data stage of IN requests.
+    /// Returns `None` if the request must be stalled.
+    pub fn handle(
+        &mut self,
+        request: RawRequest,
+        data: &mut [u8],
+        class: &mut impl ClassHandler,
+    ) -> Option<Response> {
+        if request.request_type & 0x60 != RawRequest::TYPE_STANDARD {
+            return class.request(request, data);
+        }
+        let Ok(r) = Request::try_from(request) else {
+            return class.request(request, data);
+        };
+        let len = data.len().min(request.length.into());
+        let data = &mut data[..len];
+        let configured = matches!(self.state, DeviceState::Configured { .. });
+        match r {
+            Request::GetDescriptor { ty } => {
+                let d = &self.descriptors;
+                match ty {
+                    GetDescriptor::Device => reply(data, &d.device.to_bytes()),
+                    GetDescriptor::Configuration { index, .. } => {
+                        reply(data, d.configurations.get(usize::from(index))?)
+                    }
+                    GetDescriptor::OtherSpeedConfiguration { index, .. } => {
+                        reply(data, d.other_speed_configurations.get(usize::from(index))?)
+                    }
+                    GetDescriptor::DeviceQualifier => reply(data, &d.device_qualifier?.to_bytes()),
+                    GetDescriptor::Bos { .. } => reply(data, d.bos?),
+                    GetDescriptor::Languages if !d.languages.is_empty() => {
+                        Some(Response::In(encode_languages(d.languages, data)))
+                    }
+                    GetDescriptor::String { index, .. } => {
+                        let s = d.strings.get(usize::from(index) - 1)?;
+                        Some(Response::In(encode_string(s, data)))
+                    }
+                    GetDescriptor::Report { .. } => class.request(request, data),
+                    GetDescriptor::Languages => None,
+                }
+            }
+            Request::SetAddress { address } => {
+                self.state = match (self.state, address) {
+                    (DeviceState::Configured { .. }, _) => return None,
+                    (_, 0) => DeviceState::Default,
+                    (_, a) => DeviceState::Address(a),
+                };
+                Some(Response::AckSetAddress(address))
+            }
+            Request::GetConfiguration => match self.state {
+                DeviceState::Default => None,
+                DeviceState::Address(_) => reply(data, &[0]),
+                DeviceState::Configured { configuration, .. } => reply(data, &[configuration]),
+            },
+            Request::SetConfiguration { value } => {
+                let address = match self.state {
+                    DeviceState::Default => return None,
+                    DeviceState::Address(a) | DeviceState::Configured { address: a, .. } => a,
+                };
+                self.state = if value == 0 {
+                    DeviceState::Address(address)
+                } else {
+                    self.find(value)?;
+                    DeviceState::Configured {
+                        address,
+                        configuration: value,
+                    }
+                };
+                self.alternate_settings = [0; 32];
+                self.halted = 0;
+                class.configured(value);
+                Some(Response::Ack)
+            }
+            Request::GetInterface { interface } if configured => {
+                self.configuration()?.interface(interface, 0)?;
+                reply(
+                    data,
+                    &[*self.alternate_settings.get(usize::from(interface))?],
+                )
+            }
+            Request::SetInterface {
+                interface,
+                alternate_setting,
+            } if configured => {
+                self.configuration()?
+                    .interface(interface, alternate_setting)?;
+                *self.alternate_settings.get_mut(usize::from(interface))? = alternate_setting;
+                class.interface(interface, alternate_setting);
+                Some(Response::Ack)
+            }
+            // Interfaces only exist in the Configured state.
+            Request::GetInterface { .. } | Request::SetInterface { .. } => None,
+            Request::GetStatus { recipient } => match recipient {
+                Recipient::Device => reply(
+                    data,
+                    &[
+                        u8::from(self.self_powered) | u8::from(self.remote_wakeup) << 1,
+                        0,
+                    ],
+                ),
+                Recipient::Interface(i) if configured => {
+                    self.configuration()?.interface(i, 0)?;
+                    reply(data, &[0; 2])
+                }
+                Recipient::Endpoint(e)
+                    if e.number() == EndpointNumber::N0 || self.has_endpoint(e) =>
+                {
+                    reply(data, &[self.halted(e).into(), 0])
+                }
+                _ => None,
+            },
+            Request::SetFeature { feature } | Request::ClearFeature { feature } => {
+                let set = matches!(r, Request::SetFeature { .. });
+                match feature {
+                    Feature::DeviceRemoteWakeup => self.remote_wakeup = set,
+                    // The default control pipe recovers from a stall with the next SETUP.
+                    Feature::EndpointHalt(e) if e.number() == EndpointNumber::N0 && !set => {}
+                    Feature::EndpointHalt(e) if self.has_endpoint(e) => {
+                        if set {
+                            self.halted |= e.bit();
+                        } else {
+                            self.halted &= !e.bit();
+                        }
+                        class.halt(e, set);
+                    }
+                    _ => return None,
+                }
+                Some(Response::Ack)
+            }
+            _ => class.request(request, data),
+        }
+    }
+
+    fn find(&self, value: u8) -> Option<ConfigurationTree<'a>> {
+        self.descriptors
+            .configurations
+            .iter()
+            .map(|c| ConfigurationTree::new(c).unwrap())
+            .find(|t| t.configuration().configuration_value == value)
+    }
+
+    fn has_endpoint(&self, endpoint: EndpointAddress) -> bool {
+        self.configuration().is_some_and(|t| {
+            t.interfaces()
+                .flat_map(|i| i.endpoints())
+                .any(|e| e.endpoint().address == endpoint)
+        })
+    }
+}
+
+fn reply(data: &mut [u8], src: &[u8]) -> Option<Response> {
+    let n = src.len().min(data.len());
+    data[..n].copy_from_slice(&src[..n]);
+    Some(Response::In(n))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        descriptor::{Direction, EndpointNumber},
+        enumerate::enumerate,
+        transfer::{ControlError, ControlTransfer},
+    };
+
+    const DEVICE: Device = Device {
+        usb: 0x0200,
+        class: 0,
+        subclass: 0,
+        protocol: 0,
+        max_packet_size_0: 64,
+        vendor: 0x1234,
+        product: 0x5678,
+        device: 0x0100,
+        index_manufacturer: 1,
+        index_product: 2,
+        index_serial_number: 0,
+        num_configurations: 1,
+    };
+    const CONFIGURATION: &[u8] = &[
+        0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x01, 0x00, // interface
+        0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x3f, 0x00, // hid
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+    ];
+    const DESCRIPTORS: Descriptors = Descriptors {
+        device: DEVICE,
+        configurations: &[CONFIGURATION],
+        other_speed_configurations: &[],
+        device_qualifier: None,
+        bos: None,
+        languages: &[LanguageId::ENGLISH_US],
+        strings: &["Manufacturer", "Keyboard"],
+    };
+
+    /// Answers GET_DESCRIPTOR(Report) and counts configuration changes.
+    #[derive(Default)]
+    struct Hid(u32);
+
+    impl ClassHandler for Hid {
+        fn request(&mut self, request: RawRequest, data: &mut [u8]) -> Option<Response> {
+            match Request::try_from(request).ok()? {
+                Request::GetDescriptor {
+                    ty: GetDescriptor::Report { interface: 0, .. },
+                } => {
+                    data[..2].copy_from_slice(&[0x05, 0x01]);
+                    Some(Response::In(2))
+                }
+                _ => None,
+            }
+        }
+
+        fn configured(&mut self, _: u8) {
+            self.0 += 1;
+        }
+    }
+
+    /// The handler, its class and the max packet size of the last request.
+    struct Bus<'a>(DeviceHandler<'a>, Hid, u16);
+
+    impl ControlTransfer for Bus<'_> {
+        type Error = ();
+
+        fn control(
+            &mut self,
+            address: u8,
+            max_packet_size_0: u16,
+            request: RawRequest,
+            data: &mut [u8],
+        ) -> Result<usize, ControlError<()>> {
+            let current = match self.0.state() {
+                DeviceState::Default => 0,
+                DeviceState::Address(a) | DeviceState::Configured { address: a, .. } => a,
+            };
+            if address != current {
+                return Err(ControlError::Timeout);
+            }
+            self.2 = max_packet_size_0;
+            match self.0.handle(request, data, &mut self.1) {
+                Some(Response::In(n)) => Ok(n),
+                Some(_) => Ok(0),
+                None => Err(ControlError::Stall),
+            }
+        }
+    }
+
+    #[test]
+    fn enumerate_handler() {
+        let mut bus = Bus(DeviceHandler::new(DESCRIPTORS).unwrap(), Hid::default(), 0);
+        let mut buf = [0; 128];
+        let e = enumerate(&mut bus, 3, &mut buf).unwrap();
+        assert_eq!(e.device, DEVICE);
+        assert!(e.product.unwrap().chars().eq("Keyboard".chars()));
+        assert_eq!(
+            bus.0.state(),
+            DeviceState::Configured {
+                address: 3,
+                configuration: 1
+            }
+        );
+        assert_eq!(bus.1 .0, 1);
+        assert_eq!(bus.2, DEVICE.max_packet_size_0.into());
+    }
+
+    /// Acknowledges every request.
+    struct AckAll;
+
+    impl ClassHandler for AckAll {
+        fn request(&mut self, _: RawRequest, _: &mut [u8]) -> Option<Response> {
+            Some(Response::Ack)
+        }
+    }
+
+    #[test]
+    fn unconfigured_interface() {
+        let mut d = DeviceHandler::new(DESCRIPTORS).unwrap();
+        let mut t = |r: Request| d.handle(r.into(), &mut [0], &mut AckAll);
+        let get = Request::GetInterface { interface: 0 };
+        let set = Request::SetInterface {
+            interface: 0,
+            alternate_setting: 0,
+        };
+        assert_eq!(t(get), None);
+        t(Request::SetAddress { address: 9 });
+        assert_eq!(t(get), None);
+        assert_eq!(t(set), None);
+        assert_eq!(
+            t(Request::SetConfiguration { value: 1 }),
+            Some(Response::Ack)
+        );
+        assert_eq!(t(get), Some(Response::In(1)));
+        assert_eq!(t(set), Some(Response::Ack));
+    }
+
+    #[test]
+    fn standard_requests() {
+        let mut d = DeviceHandler::new(DESCRIPTORS).unwrap();
+        let mut buf = [0; 64];
+        let mut t = |r: Request| d.handle(r.into(), &mut buf, &mut Hid::default());
+        let ep = EndpointAddress::new(EndpointNumber::N1, Direction::In);
+        let ep2 = EndpointAddress::new(EndpointNumber::N2, Direction::In);
+        let status = |ep| Request::GetStatus {
+            recipient: Recipient::Endpoint(ep),
+        };
+        let halt = |ep| Feature::EndpointHalt(ep);
+        let report = Request::GetDescriptor {
+            ty: GetDescriptor::Report {
+                interface: 0,
+                length: 63,
+            },
+        };
+
+        assert_eq!(t(Request::SetConfiguration { value: 1 }), None);
+        assert_eq!(
+            t(Request::SetAddress { address: 9 }),
+            Some(Response::AckSetAddress(9))
+        );
+        assert_eq!(t(Request::GetConfiguration), Some(Response::In(1)));
+        assert_eq!(t(Request::SetConfiguration { value: 2 }), None);
+        assert_eq!(t(status(ep)), None);
+        let ep0 = EndpointAddress::new(EndpointNumber::N0, Direction::In);
+        assert_eq!(t(status(ep0)), Some(Response::In(2)));
+        let clear = |ep| Request::ClearFeature { feature: halt(ep) };
+        assert_eq!(t(clear(ep0)), Some(Response::Ack));
+        assert_eq!(t(Request::SetFeature { feature: halt(ep0) }), None);
+        assert_eq!(
+            t(Request::SetConfiguration { value: 1 }),
+            Some(Response::Ack)
+        );
+        assert_eq!(t(Request::SetAddress { address: 10 }), None);
+        assert_eq!(
+            t(Request::SetFeature { feature: halt(ep) }),
+            Some(Response::Ack)
+        );
+        assert_eq!(t(Request::SetFeature { feature: halt(ep2) }), None);
+        assert_eq!(t(status(ep)), Some(Response::In(2)));
+        assert_eq!(
+            t(Request::SetFeature {
+                feature: Feature::DeviceRemoteWakeup
+            }),
+            Some(Response::Ack)
+        );
+        assert_eq!(
+            t(Request::SetInterface {
+                interface: 0,
+                alternate_setting: 1
+            }),
+            None
+        );
+        assert_eq!(t(report), Some(Response::In(2)));
+        assert_eq!(
+            t(Request::SetIdle {
+                duration: 0,
+                id: 0,
+                interface: 0
+            }),
+            None
+        );
+        assert!(d.halted(ep));
+        assert!(d.remote_wakeup());
+        d.set_self_powered(true);
+        let r = d.handle(
+            Request::GetStatus {
+                recipient: Recipient::Device,
+            }
+            .into(),
+            &mut buf,
+            &mut (),
+        );
+        assert_eq!((r, &buf[..2]), (Some(Response::In(2)), &[3, 0][..]));
+        d.reset();
+        assert_eq!(d.state(), DeviceState::Default);
+        assert!(!d.halted(ep));
+    }
+}
@@ -0,0 +1,425 @@
+//! Queue heads and transfer descriptors of the Enhanced Host Controller Interface.
+//!
+//! The layouts follow section 3 of the EHCI specification, revision 1.0.
+
+use crate::{
+    descriptor::{Endpoint, EndpointNumber, EndpointTransfer},
+    le_bytes, InvalidServiceInterval, RawRequest, ServiceInterval, Speed,
+};
+
+/// Terminate bit of link pointers.
+const TERMINATE: u32 = 1;
+
+/// Where a device is attached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Topology {
+    pub speed: Speed,
+    /// Address and port of the high-speed hub whose transaction translator serves a low or
+    /// full-speed device.
+    pub transaction_translator: Option<(u8, u8)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueHead {
+    pub device_address: u8,
+    pub endpoint: EndpointNumber,
+    pub speed: Speed,
+    pub max_packet_length: u16,
+    /// Take the data toggle from the qTDs instead of the overlay.
+    pub data_toggle_control: bool,
+    /// Head of the reclamation list of the asynchronous schedule.
+    pub head: bool,
+    /// A low or full-speed control endpoint.
+    pub control_endpoint: bool,
+    /// How often the controller retries after a NAK before moving on, 0 to never stop.
+    pub nak_reload: u8,
+    /// Microframes in which a transaction or start split is issued.
+    pub s_mask: u8,
+    /// Microframes in which a complete split is issued.
+    pub c_mask: u8,
+    pub hub_address: u8,
+    pub hub_port: u8,
+    /// Transactions per microframe, 1 to 3.
+    pub mult: u8,
+}
+
+impl QueueHead {
+    /// The queue head of the default control pipe.
+    pub fn control(
+        device_address: u8,
+        max_packet_size_0: u16,
+        topology: &Topology,
+    ) -> Result<Self, InvalidQueueHead> {
+        let mut qh = Self::base(device_address, EndpointNumber::N0, topology)?;
+        qh.max_packet_length = max_packet_size_0;
+        qh.data_toggle_control = true;
+        qh.control_endpoint = topology.speed != Speed::High;
+        Ok(qh)
+    }
+
+    /// The queue head of an interrupt or bulk endpoint.
+    ///
+    /// Periodic endpoints are scheduled in microframe 0, see [`QueueHead::start_at`].
+    pub fn new(
+        device_address: u8,
+        endpoint: &Endpoint,
+        topology: &Topology,
+    ) -> Result<Self, InvalidQueueHead> {
+        let mut qh = Self::base(device_address, endpoint.address.number(), topology)?;
+        let interval = topology
+            .speed
+            .service_interval(endpoint)
+            .map_err(InvalidQueueHead::Endpoint)?;
+        let transfer = endpoint.attributes.transfer();
+        qh.max_packet_length = endpoint.max_packet_size.size();
+        match transfer {
+            EndpointTransfer::Isoch => return Err(InvalidQueueHead::Isochronous),
+            EndpointTransfer::Control => {
+                qh.data_toggle_control = true;
+                qh.control_endpoint = topology.speed != Speed::High;
+            }
+            _ => {}
+        }
+        if let ServiceInterval::Periodic { microframes } = interval {
+            // The controller must not stop at a NAK in the periodic schedule.
+            qh.nak_reload = 0;
+            qh.mult = 1 + endpoint.max_packet_size.additional_transactions();
+            (qh.s_mask, qh.c_mask) = match topology.speed {
+                Speed::High => match microframes {
+                    1 => (0xff, 0),
+                    2 => (0x55, 0),
+                    4 => (0x11, 0),
+                    _ => (0x01, 0),
+                },
+                _ => (0x01, 0x1c),
+            };
+        }
+        Ok(qh)
+    }
+
+    fn base(
+        device_address: u8,
+        endpoint: EndpointNumber,
+        topology: &Topology,
+    ) -> Result<Self, InvalidQueueHead> {
+        let (hub_address, hub_port) = match (topology.speed, topology.transaction_translator) {
+            (Speed::High, _) => (0, 0),
+            (Speed::Low | Speed::Full, Some(tt)) => tt,
+            (Speed::Low | Speed::Full, None) => {
+                return Err(InvalidQueueHead::NoTransactionTranslator)
+            }
+            (Speed::Super | Speed::SuperPlus, _) => return Err(InvalidQueueHead::Speed),
+        };
+        Ok(Self {
+            device_address,
+            endpoint,
+            speed: topology.speed,
+            max_packet_length: 0,
+            data_toggle_control: false,
+            head: false,
+            control_endpoint: false,
+            nak_reload: if topology.speed == Speed::High { 4 } else { 0 },
+            s_mask: 0,
+            c_mask: 0,
+            hub_address,
+            hub_port,
+            mult: 1,
+        })
+    }
+
+    /// Move the transactions of a periodic endpoint to start in `microframe`.
+    ///
+    /// Complete splits must end in the same frame, so `microframe` is at most 3 for low and
+    /// full-speed endpoints.
+    pub fn start_at(&mut self, microframe: u8) {
+        assert!(microframe < 8, "invalid microframe");
+        if self.c_mask != 0 {
+            assert!(microframe <= 3, "complete splits would cross the frame");
+            self.c_mask <<= microframe;
+        }
+        self.s_mask = self.s_mask.rotate_left(microframe.into());
+    }
+
+    /// The Endpoint Characteristics word.
+    pub const fn characteristics(&self) -> u32 {
+        let eps = match self.speed {
+            Speed::Full => 0,
+            Speed::Low => 1,
+            _ => 2,
+        };
+        (self.device_address & 0x7f) as u32
+            | (self.endpoint as u32) << 8
+            | eps << 12
+            | (self.data_toggle_control as u32) << 14
+            | (self.head as u32) << 15
+            | ((self.max_packet_length & 0x7ff) as u32) << 16
+            | (self.control_endpoint as u32) << 27
+            | ((self.nak_reload & 0xf) as u32) << 28
+    }
+
+    /// The Endpoint Capabilities word.
+    pub const fn capabilities(&self) -> u32 {
+        self.s_mask as u32
+            | (self.c_mask as u32) << 8
+            | ((self.hub_address & 0x7f) as u32) << 16
+            | ((self.hub_port & 0x7f) as u32) << 23
+            | ((self.mult & 0x3) as u32) << 30
+    }
+
+    /// Encode with an empty transfer overlay that continues at `next_qtd`.
+    pub const fn to_bytes(&self, horizontal_link: u32, next_qtd: u32) -> [u8; 48] {
+        le_bytes([
+            horizontal_link,
+            self.characteristics(),
+            self.capabilities(),
+            0,
+            next_qtd,
+            TERMINATE,
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidQueueHead {
+    Endpoint(InvalidServiceInterval),
+    /// Isochronous endpoints use iTDs and siTDs instead.
+    Isochronous,
+    /// A low or full-speed device without a transaction translator.
+    NoTransactionTranslator,
+    /// SuperSpeed devices can't be driven by EHCI.
+    Speed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pid {
+    Out = 0,
+    In = 1,
+    Setup = 2,
+}
+
+/// A queue element transfer descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Qtd {
+    pub next: u32,
+    /// The qTD to continue at after a short packet.
+    pub alternate_next: u32,
+    pub token: u32,
+    pub buffers: [u32; 5],
+}
+
+impl Qtd {
+    /// An active qTD with 3 retries on errors.
+    ///
+    /// Returns `None` if the buffer at `address` crosses more than 5 pages or ends beyond 4 GiB.
+    pub const fn new(pid: Pid, toggle: bool, address: u32, length: u16) -> Option<Self> {
+        if (address & 0xfff) as usize + length as usize > 5 * 0x1000
+            || address as u64 + length as u64 > 1 << 32
+        {
+            return None;
+        }
+        // Pages beyond 4 GiB are not part of the buffer and stay 0.
+        let mut buffers = [0; 5];
+        let mut i = 0;
+        while i < 5 {
+            if let Some(page) = (address & !0xfff).checked_add(i as u32 * 0x1000) {
+                buffers[i] = page;
+            }
+            i += 1;
+        }
+        buffers[0] = address;
+        Some(Self {
+            next: TERMINATE,
+            alternate_next: TERMINATE,
+            token: 1 << 7
+                | (pid as u32) << 8
+                | 3 << 10
+                | (length as u32) << 16
+                | (toggle as u32) << 31,
+            buffers,
+        })
+    }
+
+    /// Interrupt on completion.
+    pub const fn with_ioc(mut self) -> Self {
+        self.token |= 1 << 15;
+        self
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        le_bytes([
+            self.next,
+            self.alternate_next,
+            self.token,
+            self.buffers[0],
+            self.buffers[1],
+            self.buffers[2],
+            self.buffers[3],
+            self.buffers[4],
+        ])
+    }
+}
+
+/// The setup, data and status stage of a control transfer.
+#[derive(Clone, Copy, Debug)]
+pub struct ControlQtds {
+    qtds: [Qtd; 3],
+    len: usize,
+}
+
+impl ControlQtds {
+    /// Chain the stages of `request`.
+    ///
+    /// `setup` holds the 8 byte setup packet and `data` the buffer of the data stage. The qTDs
+    /// will be placed consecutively at `base`. Only the status stage interrupts on completion.
+    ///
+    /// Returns `None` if the data buffer crosses more than 5 pages, or if a buffer or the qTDs
+    /// don't fit below 4 GiB.
+    pub fn new(request: &RawRequest, setup: u32, data: u32, base: u32) -> Option<Self> {
+        let (data_pid, status_pid) = match request.direction_in() {
+            true => (Pid::In, Pid::Out),
+            false => (Pid::Out, Pid::In),
+        };
+        let mut qtds = [Qtd::new(Pid::Setup, false, setup, 8)?; 3];
+        let mut len = 1;
+        if request.length != 0 {
+            qtds[1] = Qtd::new(data_pid, true, data, request.length)?;
+            len = 2;
+        }
+        let status_pid = if len == 1 { Pid::In } else { status_pid };
+        qtds[len] = Qtd::new(status_pid, true, 0, 0)?.with_ioc();
+        len += 1;
+        let at = |i: usize| base.checked_add(i as u32 * 32);
+        for (i, q) in qtds[..len - 1].iter_mut().enumerate() {
+            q.next = at(i + 1)?;
+        }
+        // A short read skips to the status stage.
+        qtds[1].alternate_next = match len {
+            3 => at(2)?,
+            _ => TERMINATE,
+        };
+        Some(Self { qtds, len })
+    }
+
+    pub fn qtds(&self) -> &[Qtd] {
+        &self.qtds[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        descriptor::{Direction, EndpointAddress, GetDescriptor, MaxPacketSize},
+        Request,
+    };
+
+    const ADDRESS: EndpointAddress = EndpointAddress::new(EndpointNumber::N1, Direction::In);
+
+    #[test]
+    fn queue_head() {
+        let high = Topology {
+            speed: Speed::High,
+            transaction_translator: None,
+        };
+        let full = Topology {
+            speed: Speed::Full,
+            transaction_translator: Some((2, 3)),
+        };
+        let qh = QueueHead::control(5, 64, &high).unwrap();
+        assert_eq!(
+            (qh.characteristics(), qh.capabilities()),
+            (0x40406005, 0x40000000)
+        );
+
+        let mut qh = QueueHead::new(
+            7,
+            &Endpoint::new(
+                ADDRESS,
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(8, 0),
+                10,
+            ),
+            &full,
+        )
+        .unwrap();
+        assert_eq!(
+            (qh.characteristics(), qh.capabilities()),
+            (0x00080107, 0x41821c01)
+        );
+        qh.start_at(2);
+        assert_eq!((qh.s_mask, qh.c_mask), (0x04, 0x70));
+        let b = qh.to_bytes(0x1002, 0x2000);
+        assert_eq!(b[..8], [0x02, 0x10, 0, 0, 0x07, 0x01, 0x08, 0x00]);
+        assert_eq!(b[16..24], [0, 0x20, 0, 0, 1, 0, 0, 0]);
+
+        let mut qh = QueueHead::new(
+            7,
+            &Endpoint::new(
+                ADDRESS,
+                EndpointTransfer::Interrupt,
+                MaxPacketSize::new(64, 0),
+                2,
+            ),
+            &high,
+        )
+        .unwrap();
+        assert_eq!((qh.s_mask, qh.c_mask, qh.nak_reload), (0x55, 0, 0));
+        qh.start_at(1);
+        assert_eq!(qh.s_mask, 0xaa);
+
+        let full = Topology {
+            transaction_translator: None,
+            ..full
+        };
+        assert!(matches!(
+            QueueHead::control(5, 64, &full),
+            Err(InvalidQueueHead::NoTransactionTranslator)
+        ));
+        assert!(matches!(
+            QueueHead::new(
+                7,
+                &Endpoint::new(
+                    ADDRESS,
+                    EndpointTransfer::Interrupt,
+                    MaxPacketSize::new(2048 - 1, 0),
+                    1
+                ),
+                &high
+            ),
+            Err(InvalidQueueHead::Endpoint(_))
+        ));
+    }
+
+    #[test]
+    fn control_qtds() {
+        let r = RawRequest::from(Request::GetDescriptor {
+            ty: GetDescriptor::Device,
+        });
+        let c = ControlQtds::new(&r, 0x1000, 0x2ff8, 0x3000).unwrap();
+        let q: [Qtd; 3] = c.qtds().try_into().unwrap();
+        assert_eq!(q.map(|q| q.next), [0x3020, 0x3040, 1]);
+        assert_eq!(q[1].alternate_next, 0x3040);
+        assert_eq!(q.map(|q| q.token), [0x00080e80, 0x80120d80, 0x80008c80]);
+        assert_eq!(q[1].buffers[..2], [0x2ff8, 0x3000]);
+        assert_eq!(q[0].to_bytes()[8..16], [0x80, 0x0e, 0x08, 0, 0, 0x10, 0, 0]);
+
+        let r = RawRequest::from(Request::SetConfiguration { value: 1 });
+        let c = ControlQtds::new(&r, 0x1000, 0, 0x3000).unwrap();
+        let q = c.qtds();
+        assert_eq!(q.len(), 2);
+        assert_eq!((q[0].next, q[1].next), (0x3020, 1));
+        assert_eq!(q[1].token, 0x80008d80);
+
+        let r = RawRequest {
+            length: 0x5000,
+            ..r
+        };
+        assert!(ControlQtds::new(&r, 0x1000, 0x2001, 0x3000).is_none());
+        let r = RawRequest { length: 8, ..r };
+        assert!(ControlQtds::new(&r, 0x1000, 0x2000, 0xffff_ffa0).is_some());
+        assert!(ControlQtds::new(&r, 0x1000, 0x2000, 0xffff_ffc0).is_none());
+        assert!(Qtd::new(Pid::In, true, 0xffff_f800, 0x1000).is_none());
+        let q = Qtd::new(Pid::In, true, 0xffff_f800, 0x800).unwrap();
+        assert_eq!(q.buffers, [0xffff_f800, 0, 0, 0, 0]);
+    }
+}
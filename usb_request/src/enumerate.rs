@@ -0,0 +1,577 @@
+//! Host-side enumeration of a newly attached device, independent of any I/O backend.
+//!
+//! The [`Enumerator`] yields the control requests to perform and consumes the replies:
+//!
+//! ```
+//! # use usb_request::{enumerate::*, mock::MockDevice, transfer::ControlTransfer, RawRequest};
+//! # const DEVICE: [u8; 18] = [18, 1, 0, 2, 0, 0, 0, 64, 0x34, 0x12, 0x78, 0x56, 0, 1, 0, 0, 0, 1];
+//! # const CONFIGURATION: [u8; 9] = [9, 2, 9, 0, 0, 1, 0, 0x80, 50];
+//! # let mut device = MockDevice::new(&DEVICE, &[&CONFIGURATION], &[], |_: &RawRequest| None);
+//! # struct Stall;
+//! # let mut transfer = |address, max_packet_size_0, request, data: &mut [u8]| {
+//! #     device.control(address, max_packet_size_0, request, data).map_err(|_| Stall)
+//! # };
+//! # let (address, mut storage) = (5, [0; 64]);
+//! let mut e = Enumerator::new(address, &mut storage);
+//! while let Action::Control { address, max_packet_size_0, request } = e.poll() {
+//!     match transfer(address, max_packet_size_0, request, e.buffer()) {
+//!         Ok(n) => e.complete(n)?,
+//!         Err(Stall) => e.stalled()?,
+//!     }
+//! }
+//! let device = e.finish()?;
+//! # assert_eq!(device.address, 5);
+//! # Ok::<(), EnumerationError>(())
+//! ```
+//!
+//! [`enumerate`] and [`enumerate_async`] do this with a [`ControlTransfer`] implementation.
+
+use crate::{
+    descriptor::{
+        self, decode, ConfigurationTree, Descriptor, Device, DevicePrefix, GetDescriptor,
+        InvalidDescriptor, InvalidDevice, InvalidTree, LanguageId, StringIter,
+    },
+    transfer::{AsyncControlTransfer, ControlError, ControlTransfer},
+    RawRequest, Request,
+};
+
+/// What to do next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Perform `request` on the default control pipe of `address`.
+    ///
+    /// The data stage of IN requests must be read into [`Enumerator::buffer`].
+    /// After SET_ADDRESS the device must be given 2 ms to recover before the next request.
+    Control {
+        address: u8,
+        max_packet_size_0: u16,
+        request: RawRequest,
+    },
+    /// Enumeration is complete, see [`Enumerator::finish`].
+    Done,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    DevicePrefix,
+    SetAddress,
+    Device,
+    ConfigurationHeader,
+    Configuration,
+    Languages,
+    String(u8),
+    SetConfiguration,
+    Done,
+}
+
+/// Drives the standard enumeration sequence.
+///
+/// The configuration descriptor at index 0 and the manufacturer, product and serial number
+/// strings are stored in the caller-provided buffer.
+pub struct Enumerator<'a> {
+    state: State,
+    address: u8,
+    max_packet_size_0: u16,
+    device: Option<Device>,
+    configuration_value: u8,
+    total_length: u16,
+    language: Option<LanguageId>,
+    strings: [Option<(usize, usize)>; 3],
+    scratch: [u8; 18],
+    buf: &'a mut [u8],
+    used: usize,
+}
+
+impl<'a> Enumerator<'a> {
+    /// Enumerate the device at address 0 and assign it `address`.
+    ///
+    /// `address` must be in the range `1..=127` and `buf` must hold at least 9 bytes.
+    pub fn new(address: u8, buf: &'a mut [u8]) -> Self {
+        assert!((1..=127).contains(&address), "invalid address");
+        assert!(buf.len() >= 9, "buffer too small");
+        Self {
+            state: State::DevicePrefix,
+            address,
+            max_packet_size_0: 8,
+            device: None,
+            configuration_value: 0,
+            total_length: 0,
+            language: None,
+            strings: [None; 3],
+            scratch: [0; 18],
+            buf,
+            used: 0,
+        }
+    }
+
+    /// The next action to perform.
+    pub fn poll(&self) -> Action {
+        let get = |ty| RawRequest::from(Request::GetDescriptor { ty });
+        let request = match self.state {
+            State::DevicePrefix => RawRequest {
+                length: 8,
+                ..get(GetDescriptor::Device)
+            },
+            State::SetAddress => Request::SetAddress {
+                address: self.address,
+            }
+            .into(),
+            State::Device => get(GetDescriptor::Device),
+            State::ConfigurationHeader => get(GetDescriptor::Configuration {
+                index: 0,
+                total_length: None,
+            }),
+            State::Configuration => get(GetDescriptor::Configuration {
+                index: 0,
+                total_length: Some(self.total_length),
+            }),
+            State::Languages => RawRequest {
+                length: self.free().min(255) as u16,
+                ..get(GetDescriptor::Languages)
+            },
+            State::String(i) => RawRequest {
+                length: self.free().min(255) as u16,
+                ..get(GetDescriptor::String {
+                    index: self.string_index(i),
+                    language: self.language.unwrap(),
+                })
+            },
+            State::SetConfiguration => Request::SetConfiguration {
+                value: self.configuration_value,
+            }
+            .into(),
+            State::Done => return Action::Done,
+        };
+        Action::Control {
+            address: match self.state {
+                State::DevicePrefix | State::SetAddress => 0,
+                _ => self.address,
+            },
+            max_packet_size_0: self.max_packet_size_0,
+            request,
+        }
+    }
+
+    /// The buffer for the data stage of the current request.
+    pub fn buffer(&mut self) -> &mut [u8] {
+        let len = match self.poll() {
+            Action::Control { request, .. } if request.direction_in() => {
+                usize::from(request.length)
+            }
+            _ => 0,
+        };
+        match self.state {
+            State::DevicePrefix | State::Device => &mut self.scratch[..len],
+            _ => &mut self.buf[self.used..][..len],
+        }
+    }
+
+    /// The current request completed and `n` bytes were transferred in the data stage.
+    pub fn complete(&mut self, n: usize) -> Result<(), EnumerationError> {
+        let Action::Control { request, .. } = self.poll() else {
+            return Err(EnumerationError::Done);
+        };
+        let expected = usize::from(request.length);
+        if n > expected {
+            return Err(EnumerationError::Overrun);
+        }
+        let short = |min: usize| {
+            (n >= min).then_some(()).ok_or(EnumerationError::Short {
+                expected: min,
+                received: n,
+            })
+        };
+        self.state = match self.state {
+            State::DevicePrefix => {
+                short(8)?;
+                let p = match DevicePrefix::decode(&self.scratch[..n]) {
+                    Ok(p) => p,
+                    Err(InvalidDevice::UnexpectedType) => {
+                        return Err(EnumerationError::UnexpectedDescriptor)
+                    }
+                    Err(e) => {
+                        return Err(EnumerationError::Descriptor(InvalidDescriptor::Device(e)))
+                    }
+                };
+                self.max_packet_size_0 = match (p.usb >= 0x0300, p.max_packet_size_0) {
+                    (false, n @ (8 | 16 | 32 | 64)) => n.into(),
+                    (true, 9) => 512,
+                    (_, n) => return Err(EnumerationError::InvalidMaxPacketSize(n)),
+                };
+                State::SetAddress
+            }
+            State::SetAddress => State::Device,
+            State::Device => {
+                short(18)?;
+                let d = match decode(&self.scratch).next() {
+                    Some(Ok(Descriptor::Device(d))) => d,
+                    Some(Err(e)) => return Err(EnumerationError::Descriptor(e)),
+                    _ => return Err(EnumerationError::UnexpectedDescriptor),
+                };
+                self.device = Some(d);
+                State::ConfigurationHeader
+            }
+            State::ConfigurationHeader => {
+                short(9)?;
+                let c = match decode(&self.buf[..n]).next() {
+                    Some(Ok(Descriptor::Configuration(c))) => c,
+                    Some(Err(e)) => return Err(EnumerationError::Descriptor(e)),
+                    _ => return Err(EnumerationError::UnexpectedDescriptor),
+                };
+                if usize::from(c.total_length) > self.buf.len() {
+                    return Err(EnumerationError::BufferTooSmall {
+                        required: c.total_length.into(),
+                    });
+                }
+                self.total_length = c.total_length;
+                self.configuration_value = c.configuration_value;
+                State::Configuration
+            }
+            State::Configuration => {
+                short(expected)?;
+                ConfigurationTree::new(&self.buf[..n]).map_err(EnumerationError::Tree)?;
+                self.used = n;
+                self.next_string(0)
+            }
+            State::Languages => {
+                let s = self.string(n)?;
+                let language = s
+                    .languages()
+                    .find(|l| *l == LanguageId::ENGLISH_US)
+                    .or_else(|| s.languages().next());
+                self.language = language;
+                match self.language {
+                    Some(_) => self.next_string(0),
+                    None => State::SetConfiguration,
+                }
+            }
+            State::String(i) => {
+                let len = 2 + self.string(n)?.len() * 2;
+                self.strings[usize::from(i)] = Some((self.used + 2, self.used + len));
+                self.used += len;
+                self.next_string(i + 1)
+            }
+            State::SetConfiguration => State::Done,
+            State::Done => unreachable!(),
+        };
+        Ok(())
+    }
+
+    /// The current request stalled.
+    ///
+    /// Strings are optional, so a stall while reading them skips the string.
+    pub fn stalled(&mut self) -> Result<(), EnumerationError> {
+        self.state = match self.state {
+            State::Languages => State::SetConfiguration,
+            State::String(i) => self.next_string(i + 1),
+            State::Done => return Err(EnumerationError::Done),
+            _ => return Err(EnumerationError::Stalled),
+        };
+        Ok(())
+    }
+
+    /// The enumerated device, once [`Action::Done`] is returned.
+    pub fn finish(self) -> Result<EnumeratedDevice<'a>, EnumerationError> {
+        if !matches!(self.state, State::Done) {
+            return Err(EnumerationError::Incomplete);
+        }
+        let buf: &'a [u8] = self.buf;
+        let string =
+            |i: usize| self.strings[i].map(|(s, e)| StringIter::from_raw(&buf[s..e]).unwrap());
+        Ok(EnumeratedDevice {
+            address: self.address,
+            device: self.device.unwrap(),
+            configuration: ConfigurationTree::new(&buf[..self.total_length.into()])
+                .map_err(EnumerationError::Tree)?,
+            language: self.language,
+            manufacturer: string(0),
+            product: string(1),
+            serial_number: string(2),
+        })
+    }
+
+    fn free(&self) -> usize {
+        self.buf.len() - self.used
+    }
+
+    fn string_index(&self, i: u8) -> u8 {
+        let d = self.device.as_ref().unwrap();
+        [d.index_manufacturer, d.index_product, d.index_serial_number][usize::from(i)]
+    }
+
+    /// The next string to read, starting at `i`.
+    fn next_string(&self, i: u8) -> State {
+        match (i..3).find(|&i| self.string_index(i) != 0) {
+            Some(_) if self.free() < 2 => State::SetConfiguration,
+            Some(_) if self.language.is_none() => State::Languages,
+            Some(i) => State::String(i),
+            _ => State::SetConfiguration,
+        }
+    }
+
+    /// Parse the string descriptor that was read into the free part of the buffer.
+    ///
+    /// Strings that did not fit are truncated.
+    fn string(&self, n: usize) -> Result<StringIter<'_>, EnumerationError> {
+        let b = &self.buf[self.used..][..n];
+        if n < 2 || b[0] < 2 {
+            return Err(EnumerationError::Short {
+                expected: 2,
+                received: n,
+            });
+        }
+        if b[1] != descriptor::STRING {
+            return Err(EnumerationError::UnexpectedDescriptor);
+        }
+        let len = usize::from(b[0]).min(n) & !1;
+        Ok(StringIter::from_raw(&b[2..len]).unwrap())
+    }
+}
+
+/// The result of a successful enumeration.
+#[derive(Debug)]
+pub struct EnumeratedDevice<'a> {
+    pub address: u8,
+    pub device: Device,
+    pub configuration: ConfigurationTree<'a>,
+    /// The language of the strings.
+    pub language: Option<LanguageId>,
+    pub manufacturer: Option<StringIter<'a>>,
+    pub product: Option<StringIter<'a>>,
+    pub serial_number: Option<StringIter<'a>>,
+}
+
+#[derive(Debug)]
+pub enum EnumerationError {
+    /// The device returned fewer bytes than required.
+    Short {
+        expected: usize,
+        received: usize,
+    },
+    /// More bytes were transferred than requested.
+    Overrun,
+    /// A required request stalled.
+    Stalled,
+    InvalidMaxPacketSize(u8),
+    UnexpectedDescriptor,
+    Descriptor(InvalidDescriptor),
+    Tree(InvalidTree),
+    /// The configuration does not fit in the buffer.
+    BufferTooSmall {
+        required: usize,
+    },
+    /// Enumeration is already done.
+    Done,
+    /// Enumeration is not done yet.
+    Incomplete,
+}
+
+/// Enumerate the device behind `t` and assign it `address`.
+pub fn enumerate<'a, T: ControlTransfer>(
+    t: &mut T,
+    address: u8,
+    buf: &'a mut [u8],
+) -> Result<EnumeratedDevice<'a>, EnumerateError<T::Error>> {
+    let mut e = Enumerator::new(address, buf);
+    while let Action::Control {
+        address,
+        max_packet_size_0,
+        request,
+    } = e.poll()
+    {
+        match t.control(address, max_packet_size_0, request, e.buffer()) {
+            Ok(n) => e.complete(n)?,
+            Err(ControlError::Stall) => e.stalled()?,
+            Err(err) => return Err(EnumerateError::Transfer(err)),
+        }
+    }
+    Ok(e.finish()?)
+}
+
+/// Like [`enumerate`], but asynchronous.
+pub async fn enumerate_async<'a, T: AsyncControlTransfer>(
+    t: &mut T,
+    address: u8,
+    buf: &'a mut [u8],
+) -> Result<EnumeratedDevice<'a>, EnumerateError<T::Error>> {
+    let mut e = Enumerator::new(address, buf);
+    while let Action::Control {
+        address,
+        max_packet_size_0,
+        request,
+    } = e.poll()
+    {
+        match t
+            .control(address, max_packet_size_0, request, e.buffer())
+            .await
+        {
+            Ok(n) => e.complete(n)?,
+            Err(ControlError::Stall) => e.stalled()?,
+            Err(err) => return Err(EnumerateError::Transfer(err)),
+        }
+    }
+    Ok(e.finish()?)
+}
+
+#[derive(Debug)]
+pub enum EnumerateError<E> {
+    Transfer(ControlError<E>),
+    Enumeration(EnumerationError),
+}
+
+impl<E> From<EnumerationError> for EnumerateError<E> {
+    fn from(e: EnumerationError) -> Self {
+        Self::Enumeration(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DEVICE: [u8; 18] = [
+        0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x34, 0x12, 0x78, 0x56, 0x00, 0x01, 0x01,
+        0x02, 0x03, 0x01,
+    ];
+    const CONFIGURATION: [u8; 25] = [
+        0x09, 0x02, 0x19, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+    ];
+
+    /// Answer `r` like a simple device would, `None` is a stall.
+    fn respond(address: u8, r: RawRequest, buf: &mut [u8]) -> Option<usize> {
+        let data: &[u8] = match Request::try_from(r).unwrap() {
+            Request::SetAddress { address: 5 } if address == 0 => &[],
+            Request::SetConfiguration { value: 1 } if address == 5 => &[],
+            Request::GetDescriptor { ty } => match ty {
+                GetDescriptor::Device => &DEVICE,
+                GetDescriptor::Configuration { index: 0, .. } => &CONFIGURATION,
+                GetDescriptor::Languages => &[4, 3, 0x07, 0x04],
+                GetDescriptor::String { index: 1, language } => {
+                    assert_eq!(language, LanguageId::GERMAN);
+                    &[8, 3, b'A', 0, b'b', 0, b'c', 0]
+                }
+                GetDescriptor::String { index: 2, .. } => &[4, 3, b'K', 0],
+                _ => return None,
+            },
+            r => panic!("{:?}", r),
+        };
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Some(n)
+    }
+
+    fn run(e: &mut Enumerator<'_>) -> Result<(), EnumerationError> {
+        while let Action::Control {
+            address, request, ..
+        } = e.poll()
+        {
+            match respond(address, request, e.buffer()) {
+                Some(n) => e.complete(n)?,
+                None => e.stalled()?,
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enumerate() {
+        let mut buf = [0; 64];
+        let mut e = Enumerator::new(5, &mut buf);
+        run(&mut e).unwrap();
+        let d = e.finish().unwrap();
+        assert_eq!(d.address, 5);
+        assert_eq!(d.device.vendor, 0x1234);
+        assert_eq!(d.configuration.configuration().configuration_value, 1);
+        assert_eq!(d.configuration.interfaces().count(), 1);
+        assert_eq!(d.language, Some(LanguageId::GERMAN));
+        assert!(d.manufacturer.unwrap().chars().eq("Abc".chars()));
+        assert!(d.product.unwrap().chars().eq("K".chars()));
+        // String 3 stalled.
+        assert!(d.serial_number.is_none());
+    }
+
+    #[test]
+    fn small_buffer() {
+        // The manufacturer string is truncated and the product string skipped.
+        let mut buf = [0; 31];
+        let mut e = Enumerator::new(5, &mut buf);
+        run(&mut e).unwrap();
+        let d = e.finish().unwrap();
+        assert!(d.manufacturer.unwrap().chars().eq("Ab".chars()));
+        assert!(d.product.is_none());
+
+        let mut buf = [0; 24];
+        let mut e = Enumerator::new(5, &mut buf);
+        assert!(matches!(
+            run(&mut e),
+            Err(EnumerationError::BufferTooSmall { required: 25 })
+        ));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut buf = [0; 64];
+        let mut e = Enumerator::new(5, &mut buf);
+        assert!(matches!(
+            e.complete(7),
+            Err(EnumerationError::Short {
+                expected: 8,
+                received: 7
+            })
+        ));
+        e.buffer().copy_from_slice(&[8, 1, 0, 2, 0, 0, 0, 63]);
+        assert!(matches!(
+            e.complete(8),
+            Err(EnumerationError::InvalidMaxPacketSize(63))
+        ));
+        e.buffer()[7] = 64;
+        e.complete(8).unwrap();
+        assert!(matches!(e.stalled(), Err(EnumerationError::Stalled)));
+        assert!(matches!(e.finish(), Err(EnumerationError::Incomplete)));
+    }
+
+    #[test]
+    fn mock() {
+        use crate::mock::{Fault, MockDevice};
+        use core::future::Future;
+
+        let strings: &[&[u8]] = &[&[4, 3, 0x09, 0x04], &[4, 3, b'M', 0]];
+        let mut d = MockDevice::new(&DEVICE, &[&CONFIGURATION], strings, |_: &RawRequest| None);
+        let mut buf = [0; 64];
+        let e = super::enumerate(&mut d, 5, &mut buf).unwrap();
+        assert!(e.manufacturer.unwrap().chars().eq("M".chars()));
+        assert!(e.product.is_none());
+        assert_eq!((d.address(), d.configuration()), (5, 1));
+
+        // The device descriptor prefix is cut short.
+        let script = |r: &RawRequest| (r.length == 8).then_some(Fault::Short(4));
+        let mut d = MockDevice::new(&DEVICE, &[&CONFIGURATION], strings, script);
+        assert!(matches!(
+            super::enumerate(&mut d, 5, &mut buf),
+            Err(EnumerateError::Enumeration(EnumerationError::Short {
+                expected: 8,
+                received: 4
+            }))
+        ));
+
+        let script =
+            |r: &RawRequest| (r.request == RawRequest::SET_ADDRESS).then_some(Fault::Timeout);
+        let mut d = MockDevice::new(&DEVICE, &[&CONFIGURATION], strings, script);
+        assert!(matches!(
+            super::enumerate(&mut d, 5, &mut buf),
+            Err(EnumerateError::Transfer(ControlError::Timeout))
+        ));
+
+        let mut d = MockDevice::new(&DEVICE, &[&CONFIGURATION], &[], |_: &RawRequest| None);
+        let f = core::pin::pin!(enumerate_async(&mut d, 7, &mut buf));
+        let mut cx = core::task::Context::from_waker(core::task::Waker::noop());
+        let core::task::Poll::Ready(e) = f.poll(&mut cx) else {
+            panic!()
+        };
+        let e = e.unwrap();
+        assert_eq!(e.address, 7);
+        assert_eq!(e.language, None);
+    }
+}
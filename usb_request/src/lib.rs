@@ -1,25 +1,351 @@
 #![no_std]
-#![feature(slice_as_chunks)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod bandwidth;
+pub mod class;
 pub mod descriptor;
+pub mod device;
+pub mod ehci;
+pub mod enumerate;
+pub mod matching;
+pub mod mock;
+pub mod status;
+pub mod transfer;
+pub mod xhci;
 
-#[derive(Debug)]
+use descriptor::{Direction, Endpoint, EndpointAddress, EndpointTransfer, MaxPacketSize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Request {
-    GetDescriptor { ty: descriptor::GetDescriptor },
-    SetConfiguration { value: u8 },
-    GetReport { id: u8 },
-    SetReport,
-    GetIdle,
-    SetIdle,
-    SetProtocol,
-    GetProtocol,
+    /// Get the status of a device, interface or endpoint.
+    ///
+    /// The response can be decoded with the types in [`status`].
+    GetStatus {
+        recipient: Recipient,
+    },
+    ClearFeature {
+        feature: Feature,
+    },
+    SetFeature {
+        feature: Feature,
+    },
+    /// Assign an address to the device.
+    ///
+    /// The address must be in the range `1..=127`.
+    SetAddress {
+        address: u8,
+    },
+    GetDescriptor {
+        ty: descriptor::GetDescriptor,
+    },
+    GetConfiguration,
+    SetConfiguration {
+        value: u8,
+    },
+    GetInterface {
+        interface: u8,
+    },
+    SetInterface {
+        interface: u8,
+        alternate_setting: u8,
+    },
+    SyncFrame {
+        endpoint: EndpointAddress,
+    },
+    GetReport {
+        ty: ReportType,
+        id: u8,
+        interface: u8,
+        length: u16,
+    },
+    SetReport {
+        ty: ReportType,
+        id: u8,
+        interface: u8,
+        length: u16,
+    },
+    GetIdle {
+        id: u8,
+        interface: u8,
+    },
+    /// Limit the rate at which an input report is sent if it hasn't changed.
+    ///
+    /// `duration` is in units of 4 ms. A duration of 0 means the report is only sent when it
+    /// changes. An `id` of 0 applies the duration to all reports.
+    SetIdle {
+        duration: u8,
+        id: u8,
+        interface: u8,
+    },
+    SetProtocol {
+        protocol: Protocol,
+        interface: u8,
+    },
+    GetProtocol {
+        interface: u8,
+    },
+    /// A vendor-specific request.
+    ///
+    /// The request type must have [`RawRequest::TYPE_VENDOR`] set.
+    Vendor(RawRequest),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recipient {
+    Device,
+    Interface(u8),
+    Endpoint(EndpointAddress),
+}
+
+impl Recipient {
+    fn request_type(&self) -> u8 {
+        match self {
+            Self::Device => RawRequest::RECIPIENT_DEVICE,
+            Self::Interface(_) => RawRequest::RECIPIENT_INTERFACE,
+            Self::Endpoint(_) => RawRequest::RECIPIENT_ENDPOINT,
+        }
+    }
+
+    fn index(&self) -> u16 {
+        match *self {
+            Self::Device => 0,
+            Self::Interface(i) => i.into(),
+            Self::Endpoint(e) => u8::from(e).into(),
+        }
+    }
+}
+
+/// A feature that can be enabled with SET_FEATURE or disabled with CLEAR_FEATURE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    EndpointHalt(EndpointAddress),
+    /// Allow the function of an interface to be suspended separately.
+    ///
+    /// Only SuperSpeed devices support this feature.
+    FunctionSuspend {
+        interface: u8,
+        suspend: bool,
+        remote_wakeup: bool,
+    },
+    DeviceRemoteWakeup,
+    /// Put the device in a test mode.
+    ///
+    /// Test mode cannot be cleared, the device must be power cycled instead.
+    TestMode(TestSelector),
+    U1Enable,
+    U2Enable,
+    LtmEnable,
+}
+
+impl Feature {
+    pub const ENDPOINT_HALT: u16 = 0;
+    pub const FUNCTION_SUSPEND: u16 = 0;
+    pub const DEVICE_REMOTE_WAKEUP: u16 = 1;
+    pub const TEST_MODE: u16 = 2;
+    pub const U1_ENABLE: u16 = 48;
+    pub const U2_ENABLE: u16 = 49;
+    pub const LTM_ENABLE: u16 = 50;
+
+    fn recipient(&self) -> Recipient {
+        match *self {
+            Self::EndpointHalt(e) => Recipient::Endpoint(e),
+            Self::FunctionSuspend { interface, .. } => Recipient::Interface(interface),
+            _ => Recipient::Device,
+        }
+    }
+
+    fn selector(&self) -> u16 {
+        match self {
+            Self::EndpointHalt(_) => Self::ENDPOINT_HALT,
+            Self::FunctionSuspend { .. } => Self::FUNCTION_SUSPEND,
+            Self::DeviceRemoteWakeup => Self::DEVICE_REMOTE_WAKEUP,
+            Self::TestMode(_) => Self::TEST_MODE,
+            Self::U1Enable => Self::U1_ENABLE,
+            Self::U2Enable => Self::U2_ENABLE,
+            Self::LtmEnable => Self::LTM_ENABLE,
+        }
+    }
+
+    fn index(&self) -> u16 {
+        match *self {
+            Self::FunctionSuspend {
+                interface,
+                suspend,
+                remote_wakeup,
+            } => {
+                let options = u16::from(suspend) | u16::from(remote_wakeup) << 1;
+                options << 8 | u16::from(interface)
+            }
+            Self::TestMode(t) => (t as u16) << 8,
+            f => f.recipient().index(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestSelector {
+    J = 1,
+    K = 2,
+    Se0Nak = 3,
+    Packet = 4,
+    ForceEnable = 5,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportType {
+    Input = 1,
+    Output = 2,
+    Feature = 3,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Boot = 0,
+    Report = 1,
+}
+
+/// The speed a device operates at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Speed {
+    /// 1.5 Mb/s
+    Low,
+    /// 12 Mb/s
+    Full,
+    /// 480 Mb/s
+    High,
+    /// 5 Gb/s
+    Super,
+    /// 10 Gb/s and up
+    SuperPlus,
+}
+
+impl Speed {
+    /// Decode the bInterval of `endpoint`.
+    ///
+    /// The interval and max packet size must be in range for the transfer type at this speed.
+    pub const fn service_interval(
+        self,
+        endpoint: &Endpoint,
+    ) -> Result<ServiceInterval, InvalidServiceInterval> {
+        let transfer = endpoint.attributes.transfer();
+        if !self.max_packet_size_valid(transfer, endpoint.max_packet_size) {
+            return Err(InvalidServiceInterval::MaxPacketSize(
+                endpoint.max_packet_size,
+            ));
+        }
+        if !self.interval_valid(transfer, endpoint.interval) {
+            return Err(InvalidServiceInterval::Interval(endpoint.interval));
+        }
+        let n = endpoint.interval as u32;
+        let out = matches!(endpoint.address.direction(), Direction::Out);
+        Ok(match (self, transfer) {
+            (Self::Low | Self::Full, EndpointTransfer::Interrupt) => {
+                ServiceInterval::Periodic { microframes: n * 8 }
+            }
+            (Self::Low | Self::Full, EndpointTransfer::Isoch) => ServiceInterval::Periodic {
+                microframes: 8 << (n - 1),
+            },
+            (_, EndpointTransfer::Interrupt | EndpointTransfer::Isoch) => {
+                ServiceInterval::Periodic {
+                    microframes: 1 << (n - 1),
+                }
+            }
+            (Self::High, EndpointTransfer::Control) => ServiceInterval::NakRate {
+                microframes: endpoint.interval,
+            },
+            (Self::High, EndpointTransfer::Bulk) if out => ServiceInterval::NakRate {
+                microframes: endpoint.interval,
+            },
+            _ => ServiceInterval::Aperiodic,
+        })
+    }
+
+    /// Whether `max_packet_size`, including additional transactions, is allowed.
+    pub const fn max_packet_size_valid(
+        self,
+        transfer: EndpointTransfer,
+        max_packet_size: MaxPacketSize,
+    ) -> bool {
+        let size = max_packet_size.size();
+        let additional = max_packet_size.additional_transactions();
+        let periodic = matches!(
+            transfer,
+            EndpointTransfer::Interrupt | EndpointTransfer::Isoch
+        );
+        if additional != 0 && !(matches!(self, Self::High) && periodic && additional <= 2) {
+            return false;
+        }
+        match (self, transfer) {
+            (Self::Low, EndpointTransfer::Control) => size == 8,
+            (Self::Low, EndpointTransfer::Interrupt) => size <= 8,
+            (Self::Low, _) => false,
+            (Self::Full, EndpointTransfer::Control | EndpointTransfer::Bulk) => {
+                matches!(size, 8 | 16 | 32 | 64)
+            }
+            (Self::Full, EndpointTransfer::Interrupt) => size <= 64,
+            (Self::Full, EndpointTransfer::Isoch) => size <= 1023,
+            (Self::High, EndpointTransfer::Control) => size == 64,
+            (Self::High, EndpointTransfer::Bulk) => size == 512,
+            (Self::High, _) => size <= 1024,
+            (Self::Super | Self::SuperPlus, EndpointTransfer::Control) => size == 512,
+            (Self::Super | Self::SuperPlus, EndpointTransfer::Bulk) => size == 1024,
+            (Self::Super | Self::SuperPlus, _) => size <= 1024,
+        }
+    }
+
+    /// Whether `interval` is in range for the transfer type.
+    pub const fn interval_valid(self, transfer: EndpointTransfer, interval: u8) -> bool {
+        match (self, transfer) {
+            (Self::Low | Self::Full, EndpointTransfer::Interrupt) => interval >= 1,
+            (_, EndpointTransfer::Interrupt | EndpointTransfer::Isoch) => {
+                interval >= 1 && interval <= 16
+            }
+            _ => true,
+        }
+    }
+}
+
+/// How often an endpoint is serviced, in microframes of 125 µs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceInterval {
+    /// An interrupt or isochronous endpoint is polled once every interval.
+    Periodic { microframes: u32 },
+    /// A high-speed bulk OUT or control endpoint NAKs at most once every interval.
+    ///
+    /// 0 means the endpoint never NAKs.
+    NakRate { microframes: u8 },
+    /// The endpoint is serviced whenever bandwidth is available.
+    Aperiodic,
+}
+
+impl ServiceInterval {
+    /// The polling period of a periodic endpoint in microseconds.
+    pub const fn micros(&self) -> Option<u32> {
+        match self {
+            Self::Periodic { microframes } => Some(*microframes * 125),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidServiceInterval {
+    Interval(u8),
+    MaxPacketSize(MaxPacketSize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RawRequest {
     pub request_type: u8,
     pub request: u8,
     pub value: u16,
     pub index: u16,
+    /// The amount of bytes to transfer in the data stage.
+    ///
+    /// For IN requests this is the maximum, the device may return less.
+    pub length: u16,
 }
 
 impl RawRequest {
@@ -47,32 +373,93 @@ impl RawRequest {
     pub const SET_INTERFACE: u8 = 11;
     pub const SYNC_FRAME: u8 = 12;
 
+    // HID (7.2)
+    pub const GET_REPORT: u8 = 0x1;
+    pub const GET_IDLE: u8 = 0x2;
+    pub const GET_PROTOCOL: u8 = 0x3;
+    pub const SET_REPORT: u8 = 0x9;
+    pub const SET_IDLE: u8 = 0xa;
+    pub const SET_PROTOCOL: u8 = 0xb;
+
     pub fn direction_in(&self) -> bool {
         self.request_type & Self::DIR_IN != 0
     }
+
+    /// Encode the request as a SETUP packet.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let [v0, v1] = self.value.to_le_bytes();
+        let [i0, i1] = self.index.to_le_bytes();
+        let [l0, l1] = self.length.to_le_bytes();
+        [self.request_type, self.request, v0, v1, i0, i1, l0, l1]
+    }
+
+    /// Decode a SETUP packet.
+    pub fn from_bytes(b: [u8; 8]) -> Self {
+        Self {
+            request_type: b[0],
+            request: b[1],
+            value: u16::from_le_bytes([b[2], b[3]]),
+            index: u16::from_le_bytes([b[4], b[5]]),
+            length: u16::from_le_bytes([b[6], b[7]]),
+        }
+    }
 }
 
 impl From<Request> for RawRequest {
     fn from(r: Request) -> Self {
         let w_value = |ty, i| u16::from(ty) << 8 | u16::from(i);
+        let feature = |request, f: Feature| RawRequest {
+            request_type: Self::DIR_OUT | Self::TYPE_STANDARD | f.recipient().request_type(),
+            request,
+            value: f.selector(),
+            index: f.index(),
+            length: 0,
+        };
         match r {
+            Request::GetStatus { recipient } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_STANDARD | recipient.request_type(),
+                request: Self::GET_STATUS,
+                value: 0,
+                index: recipient.index(),
+                length: 2,
+            },
+            Request::ClearFeature { feature: f } => feature(Self::CLEAR_FEATURE, f),
+            Request::SetFeature { feature: f } => feature(Self::SET_FEATURE, f),
+            Request::SetAddress { address } => RawRequest {
+                request_type: Self::DIR_OUT | Self::TYPE_STANDARD | Self::RECIPIENT_DEVICE,
+                request: Self::SET_ADDRESS,
+                value: address.into(),
+                index: 0,
+                length: 0,
+            },
             Request::GetDescriptor { ty } => {
                 use descriptor::GetDescriptor::*;
                 RawRequest {
                     request_type: Self::DIR_IN
                         | Self::TYPE_STANDARD
                         | match ty {
-                            Device | Configuration { .. } | String { .. } => Self::RECIPIENT_DEVICE,
-                            Report => Self::RECIPIENT_INTERFACE,
+                            Report { .. } => Self::RECIPIENT_INTERFACE,
+                            _ => Self::RECIPIENT_DEVICE,
                         },
                     request: Self::GET_DESCRIPTOR,
                     value: match ty {
                         Device => w_value(descriptor::DEVICE, 0),
-                        Configuration { index } => w_value(descriptor::CONFIGURATION, index),
-                        String { index } => w_value(descriptor::STRING, index),
-                        Report => w_value(descriptor::REPORT, 0),
+                        Configuration { index, .. } => w_value(descriptor::CONFIGURATION, index),
+                        Languages => w_value(descriptor::STRING, 0),
+                        String { index, .. } => w_value(descriptor::STRING, index),
+                        Report { .. } => w_value(descriptor::REPORT, 0),
+                        Bos { .. } => w_value(descriptor::BOS, 0),
+                        DeviceQualifier => w_value(descriptor::DEVICE_QUALIFIER, 0),
+                        OtherSpeedConfiguration { index, .. } => {
+                            w_value(descriptor::OTHER_SPEED_CONFIGURATION, index)
+                        }
                     },
-                    index: 0,
+                    index: match ty {
+                        String { language, .. } => language.0,
+                        Report { interface, .. } => interface.into(),
+                        _ => 0,
+                    },
+                    length: ty.length(),
                 }
             }
             Request::SetConfiguration { value } => RawRequest {
@@ -80,8 +467,758 @@ impl From<Request> for RawRequest {
                 request: Self::SET_CONFIGURATION,
                 value: value.into(),
                 index: 0,
+                length: 0,
+            },
+            Request::GetConfiguration => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_STANDARD | Self::RECIPIENT_DEVICE,
+                request: Self::GET_CONFIGURATION,
+                value: 0,
+                index: 0,
+                length: 1,
+            },
+            Request::GetInterface { interface } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_STANDARD | Self::RECIPIENT_INTERFACE,
+                request: Self::GET_INTERFACE,
+                value: 0,
+                index: interface.into(),
+                length: 1,
+            },
+            Request::SetInterface {
+                interface,
+                alternate_setting,
+            } => RawRequest {
+                request_type: Self::DIR_OUT | Self::TYPE_STANDARD | Self::RECIPIENT_INTERFACE,
+                request: Self::SET_INTERFACE,
+                value: alternate_setting.into(),
+                index: interface.into(),
+                length: 0,
             },
-            _ => todo!(),
+            Request::SyncFrame { endpoint } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_STANDARD | Self::RECIPIENT_ENDPOINT,
+                request: Self::SYNC_FRAME,
+                value: 0,
+                index: u8::from(endpoint).into(),
+                length: 2,
+            },
+            Request::GetReport {
+                ty,
+                id,
+                interface,
+                length,
+            } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::GET_REPORT,
+                value: w_value(ty as u8, id),
+                index: interface.into(),
+                length,
+            },
+            Request::SetReport {
+                ty,
+                id,
+                interface,
+                length,
+            } => RawRequest {
+                request_type: Self::DIR_OUT | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::SET_REPORT,
+                value: w_value(ty as u8, id),
+                index: interface.into(),
+                length,
+            },
+            Request::GetIdle { id, interface } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::GET_IDLE,
+                value: id.into(),
+                index: interface.into(),
+                length: 1,
+            },
+            Request::SetIdle {
+                duration,
+                id,
+                interface,
+            } => RawRequest {
+                request_type: Self::DIR_OUT | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::SET_IDLE,
+                value: w_value(duration, id),
+                index: interface.into(),
+                length: 0,
+            },
+            Request::SetProtocol {
+                protocol,
+                interface,
+            } => RawRequest {
+                request_type: Self::DIR_OUT | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::SET_PROTOCOL,
+                value: protocol as u16,
+                index: interface.into(),
+                length: 0,
+            },
+            Request::GetProtocol { interface } => RawRequest {
+                request_type: Self::DIR_IN | Self::TYPE_CLASS | Self::RECIPIENT_INTERFACE,
+                request: Self::GET_PROTOCOL,
+                value: 0,
+                index: interface.into(),
+                length: 1,
+            },
+            Request::Vendor(r) => r,
+        }
+    }
+}
+
+/// Decode a request received by a device.
+///
+/// Any request produced by `From<Request>` is decoded back to the original request.
+/// The reverse only holds if `length` is the default for the request, as the length of
+/// GET_DESCRIPTOR requests with a fixed size response is ignored.
+///
+/// Class-specific requests to an interface are decoded as HID requests by their request code.
+/// Other class-specific requests return [`InvalidRequest::UnknownRequest`] and must be decoded by
+/// the caller. Since other classes reuse the HID request codes, drivers of those classes should
+/// decode their requests before falling back to this.
+impl TryFrom<RawRequest> for Request {
+    type Error = InvalidRequest;
+
+    fn try_from(r: RawRequest) -> Result<Self, Self::Error> {
+        use InvalidRequest::*;
+        let [value_lo, value_hi] = r.value.to_le_bytes();
+        let [index_lo, index_hi] = r.index.to_le_bytes();
+        let rcpt_bits = r.request_type & 0x1f;
+        let dir = |in_| {
+            (r.direction_in() == in_)
+                .then_some(())
+                .ok_or(UnexpectedDirection)
+        };
+        let rcpt = |n| (rcpt_bits == n).then_some(()).ok_or(UnexpectedRecipient);
+        let len = |n| (r.length == n).then_some(()).ok_or(UnexpectedLength);
+        let value = |n| (r.value == n).then_some(()).ok_or(UnexpectedValue);
+        let index = |n| (r.index == n).then_some(()).ok_or(UnexpectedIndex);
+        let endpoint = |n| EndpointAddress::from_raw(n).ok_or(InvalidEndpoint);
+        let interface = || (index_hi == 0).then_some(index_lo).ok_or(UnexpectedIndex);
+        let recipient = || match rcpt_bits {
+            RawRequest::RECIPIENT_DEVICE => index(0).map(|()| Recipient::Device),
+            RawRequest::RECIPIENT_INTERFACE => interface().map(Recipient::Interface),
+            RawRequest::RECIPIENT_ENDPOINT => {
+                (index_hi == 0).then_some(()).ok_or(UnexpectedIndex)?;
+                endpoint(index_lo).map(Recipient::Endpoint)
+            }
+            _ => Err(UnexpectedRecipient),
+        };
+
+        match r.request_type & 3 << 5 {
+            RawRequest::TYPE_STANDARD => match r.request {
+                RawRequest::GET_STATUS => {
+                    dir(true)?;
+                    value(0)?;
+                    len(2)?;
+                    recipient().map(|recipient| Self::GetStatus { recipient })
+                }
+                RawRequest::CLEAR_FEATURE | RawRequest::SET_FEATURE => {
+                    dir(false)?;
+                    len(0)?;
+                    let set = r.request == RawRequest::SET_FEATURE;
+                    let is_device = rcpt_bits == RawRequest::RECIPIENT_DEVICE;
+                    let feature = if is_device && r.value == Feature::TEST_MODE {
+                        // The test selector is in the upper byte of wIndex.
+                        if !set {
+                            return Err(UnknownFeature);
+                        }
+                        (index_lo == 0).then_some(()).ok_or(UnexpectedIndex)?;
+                        Feature::TestMode(match index_hi {
+                            1 => TestSelector::J,
+                            2 => TestSelector::K,
+                            3 => TestSelector::Se0Nak,
+                            4 => TestSelector::Packet,
+                            5 => TestSelector::ForceEnable,
+                            _ => return Err(UnexpectedIndex),
+                        })
+                    } else if rcpt_bits == RawRequest::RECIPIENT_INTERFACE {
+                        // The suspend options are in the upper byte of wIndex.
+                        value(Feature::FUNCTION_SUSPEND).map_err(|_| UnknownFeature)?;
+                        (index_hi & !0x3 == 0)
+                            .then_some(())
+                            .ok_or(UnexpectedIndex)?;
+                        Feature::FunctionSuspend {
+                            interface: index_lo,
+                            suspend: index_hi & 1 != 0,
+                            remote_wakeup: index_hi & 2 != 0,
+                        }
+                    } else {
+                        match (recipient()?, r.value) {
+                            (Recipient::Endpoint(e), Feature::ENDPOINT_HALT) => {
+                                Feature::EndpointHalt(e)
+                            }
+                            (Recipient::Device, Feature::DEVICE_REMOTE_WAKEUP) => {
+                                Feature::DeviceRemoteWakeup
+                            }
+                            (Recipient::Device, Feature::U1_ENABLE) => Feature::U1Enable,
+                            (Recipient::Device, Feature::U2_ENABLE) => Feature::U2Enable,
+                            (Recipient::Device, Feature::LTM_ENABLE) => Feature::LtmEnable,
+                            (_, _) => return Err(UnknownFeature),
+                        }
+                    };
+                    Ok(if set {
+                        Self::SetFeature { feature }
+                    } else {
+                        Self::ClearFeature { feature }
+                    })
+                }
+                RawRequest::SET_ADDRESS => {
+                    dir(false)?;
+                    rcpt(RawRequest::RECIPIENT_DEVICE)?;
+                    index(0)?;
+                    len(0)?;
+                    (r.value <= 127)
+                        .then_some(Self::SetAddress { address: value_lo })
+                        .ok_or(UnexpectedValue)
+                }
+                RawRequest::GET_DESCRIPTOR => {
+                    use descriptor::GetDescriptor as D;
+                    dir(true)?;
+                    let ty = match (rcpt_bits, value_hi) {
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::DEVICE) => {
+                            value(u16::from(descriptor::DEVICE) << 8)?;
+                            index(0)?;
+                            D::Device
+                        }
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::CONFIGURATION) => {
+                            index(0)?;
+                            D::Configuration {
+                                index: value_lo,
+                                total_length: (r.length != 9).then_some(r.length),
+                            }
+                        }
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::STRING) => {
+                            if value_lo == 0 {
+                                index(0)?;
+                                D::Languages
+                            } else {
+                                D::String {
+                                    index: value_lo,
+                                    language: descriptor::LanguageId(r.index),
+                                }
+                            }
+                        }
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::BOS) => {
+                            value(u16::from(descriptor::BOS) << 8)?;
+                            index(0)?;
+                            D::Bos {
+                                total_length: (r.length != 5).then_some(r.length),
+                            }
+                        }
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::DEVICE_QUALIFIER) => {
+                            value(u16::from(descriptor::DEVICE_QUALIFIER) << 8)?;
+                            index(0)?;
+                            D::DeviceQualifier
+                        }
+                        (RawRequest::RECIPIENT_DEVICE, descriptor::OTHER_SPEED_CONFIGURATION) => {
+                            index(0)?;
+                            D::OtherSpeedConfiguration {
+                                index: value_lo,
+                                total_length: (r.length != 9).then_some(r.length),
+                            }
+                        }
+                        (RawRequest::RECIPIENT_INTERFACE, descriptor::REPORT) => D::Report {
+                            interface: interface()?,
+                            length: r.length,
+                        },
+                        _ => return Err(UnknownDescriptor),
+                    };
+                    Ok(Self::GetDescriptor { ty })
+                }
+                RawRequest::GET_CONFIGURATION => {
+                    dir(true)?;
+                    rcpt(RawRequest::RECIPIENT_DEVICE)?;
+                    value(0)?;
+                    index(0)?;
+                    len(1)?;
+                    Ok(Self::GetConfiguration)
+                }
+                RawRequest::SET_CONFIGURATION => {
+                    dir(false)?;
+                    rcpt(RawRequest::RECIPIENT_DEVICE)?;
+                    index(0)?;
+                    len(0)?;
+                    (value_hi == 0)
+                        .then_some(Self::SetConfiguration { value: value_lo })
+                        .ok_or(UnexpectedValue)
+                }
+                RawRequest::GET_INTERFACE => {
+                    dir(true)?;
+                    rcpt(RawRequest::RECIPIENT_INTERFACE)?;
+                    value(0)?;
+                    len(1)?;
+                    interface().map(|interface| Self::GetInterface { interface })
+                }
+                RawRequest::SET_INTERFACE => {
+                    dir(false)?;
+                    rcpt(RawRequest::RECIPIENT_INTERFACE)?;
+                    len(0)?;
+                    (value_hi == 0).then_some(()).ok_or(UnexpectedValue)?;
+                    Ok(Self::SetInterface {
+                        interface: interface()?,
+                        alternate_setting: value_lo,
+                    })
+                }
+                RawRequest::SYNC_FRAME => {
+                    dir(true)?;
+                    rcpt(RawRequest::RECIPIENT_ENDPOINT)?;
+                    value(0)?;
+                    len(2)?;
+                    match recipient()? {
+                        Recipient::Endpoint(endpoint) => Ok(Self::SyncFrame { endpoint }),
+                        _ => unreachable!(),
+                    }
+                }
+                _ => Err(UnknownRequest),
+            },
+            RawRequest::TYPE_CLASS => {
+                // HID requests go to an interface. Requests of other classes to entities or
+                // endpoints are left to the caller.
+                if rcpt_bits != RawRequest::RECIPIENT_INTERFACE || index_hi != 0 {
+                    return Err(UnknownRequest);
+                }
+                let interface = index_lo;
+                let report_type = || match value_hi {
+                    1 => Ok(ReportType::Input),
+                    2 => Ok(ReportType::Output),
+                    3 => Ok(ReportType::Feature),
+                    _ => Err(UnexpectedValue),
+                };
+                match r.request {
+                    RawRequest::GET_REPORT => {
+                        dir(true)?;
+                        Ok(Self::GetReport {
+                            ty: report_type()?,
+                            id: value_lo,
+                            interface,
+                            length: r.length,
+                        })
+                    }
+                    RawRequest::SET_REPORT => {
+                        dir(false)?;
+                        Ok(Self::SetReport {
+                            ty: report_type()?,
+                            id: value_lo,
+                            interface,
+                            length: r.length,
+                        })
+                    }
+                    RawRequest::GET_IDLE => {
+                        dir(true)?;
+                        len(1)?;
+                        (value_hi == 0).then_some(()).ok_or(UnexpectedValue)?;
+                        Ok(Self::GetIdle {
+                            id: value_lo,
+                            interface,
+                        })
+                    }
+                    RawRequest::SET_IDLE => {
+                        dir(false)?;
+                        len(0)?;
+                        Ok(Self::SetIdle {
+                            duration: value_hi,
+                            id: value_lo,
+                            interface,
+                        })
+                    }
+                    RawRequest::GET_PROTOCOL => {
+                        dir(true)?;
+                        value(0)?;
+                        len(1)?;
+                        Ok(Self::GetProtocol { interface })
+                    }
+                    RawRequest::SET_PROTOCOL => {
+                        dir(false)?;
+                        len(0)?;
+                        let protocol = match r.value {
+                            0 => Protocol::Boot,
+                            1 => Protocol::Report,
+                            _ => return Err(UnexpectedValue),
+                        };
+                        Ok(Self::SetProtocol {
+                            protocol,
+                            interface,
+                        })
+                    }
+                    _ => Err(UnknownRequest),
+                }
+            }
+            RawRequest::TYPE_VENDOR => Ok(Self::Vendor(r)),
+            _ => Err(ReservedType),
         }
     }
 }
+
+#[derive(Debug)]
+pub enum InvalidRequest {
+    /// The type field of the request type has the reserved value 3.
+    ReservedType,
+    UnknownRequest,
+    UnknownDescriptor,
+    UnknownFeature,
+    UnexpectedDirection,
+    UnexpectedRecipient,
+    UnexpectedValue,
+    UnexpectedIndex,
+    UnexpectedLength,
+    InvalidEndpoint,
+}
+
+/// Encode the little endian `words` of a host controller data structure, padded with zeroes to
+/// `N` bytes.
+pub(crate) const fn le_bytes<const W: usize, const N: usize>(words: [u32; W]) -> [u8; N] {
+    assert!(W * 4 <= N);
+    let mut b = [0; N];
+    let mut i = 0;
+    while i < W * 4 {
+        b[i] = words[i / 4].to_le_bytes()[i % 4];
+        i += 1;
+    }
+    b
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn setup_packet() {
+        let r = RawRequest::from(Request::GetDescriptor {
+            ty: descriptor::GetDescriptor::Device,
+        });
+        let b = r.to_bytes();
+        assert_eq!(b, [0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00]);
+        assert_eq!(RawRequest::from_bytes(b), r);
+    }
+
+    #[test]
+    fn configuration_length() {
+        let f = |total_length| {
+            RawRequest::from(Request::GetDescriptor {
+                ty: descriptor::GetDescriptor::Configuration {
+                    index: 1,
+                    total_length,
+                },
+            })
+            .to_bytes()
+        };
+        assert_eq!(f(None), [0x80, 0x06, 0x01, 0x02, 0x00, 0x00, 0x09, 0x00]);
+        assert_eq!(
+            f(Some(0x122)),
+            [0x80, 0x06, 0x01, 0x02, 0x00, 0x00, 0x22, 0x01]
+        );
+    }
+
+    #[test]
+    fn standard_requests() {
+        use descriptor::{Direction, EndpointNumber};
+        let ep = EndpointAddress::new(EndpointNumber::N2, Direction::In);
+        let f = |r| RawRequest::from(r).to_bytes();
+        assert_eq!(
+            f(Request::SetAddress { address: 5 }),
+            [0x00, 0x05, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            f(Request::ClearFeature {
+                feature: Feature::EndpointHalt(ep)
+            }),
+            [0x02, 0x01, 0x00, 0x00, 0x82, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            f(Request::SetFeature {
+                feature: Feature::TestMode(TestSelector::Packet)
+            }),
+            [0x00, 0x03, 0x02, 0x00, 0x00, 0x04, 0x00, 0x00]
+        );
+        assert_eq!(
+            f(Request::SetFeature {
+                feature: Feature::FunctionSuspend {
+                    interface: 3,
+                    suspend: true,
+                    remote_wakeup: true,
+                }
+            }),
+            [0x01, 0x03, 0x00, 0x00, 0x03, 0x03, 0x00, 0x00]
+        );
+        assert_eq!(
+            f(Request::SetInterface {
+                interface: 1,
+                alternate_setting: 2
+            }),
+            [0x01, 0x0b, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            f(Request::GetStatus {
+                recipient: Recipient::Endpoint(ep)
+            }),
+            [0x82, 0x00, 0x00, 0x00, 0x82, 0x00, 0x02, 0x00]
+        );
+    }
+
+    #[track_caller]
+    fn round_trip(r: Request) {
+        let raw = RawRequest::from(r);
+        assert_eq!(Request::try_from(raw).unwrap(), r);
+        assert_eq!(RawRequest::from_bytes(raw.to_bytes()), raw);
+    }
+
+    #[test]
+    fn decode() {
+        use descriptor::{Direction, EndpointNumber, GetDescriptor};
+        let ep = EndpointAddress::new(EndpointNumber::N1, Direction::Out);
+        [
+            Request::GetStatus {
+                recipient: Recipient::Device,
+            },
+            Request::GetStatus {
+                recipient: Recipient::Interface(4),
+            },
+            Request::ClearFeature {
+                feature: Feature::EndpointHalt(ep),
+            },
+            Request::SetFeature {
+                feature: Feature::DeviceRemoteWakeup,
+            },
+            Request::SetFeature {
+                feature: Feature::TestMode(TestSelector::Se0Nak),
+            },
+            Request::SetFeature {
+                feature: Feature::FunctionSuspend {
+                    interface: 1,
+                    suspend: true,
+                    remote_wakeup: false,
+                },
+            },
+            Request::SetFeature {
+                feature: Feature::U2Enable,
+            },
+            Request::SetAddress { address: 127 },
+            Request::GetDescriptor {
+                ty: GetDescriptor::Device,
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::Configuration {
+                    index: 0,
+                    total_length: Some(59),
+                },
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::Languages,
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::String {
+                    index: 2,
+                    language: descriptor::LanguageId::ENGLISH_US,
+                },
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::Report {
+                    interface: 1,
+                    length: 63,
+                },
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::Bos {
+                    total_length: Some(22),
+                },
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::DeviceQualifier,
+            },
+            Request::GetDescriptor {
+                ty: GetDescriptor::OtherSpeedConfiguration {
+                    index: 0,
+                    total_length: None,
+                },
+            },
+            Request::GetConfiguration,
+            Request::SetConfiguration { value: 1 },
+            Request::GetInterface { interface: 3 },
+            Request::SetInterface {
+                interface: 3,
+                alternate_setting: 1,
+            },
+            Request::SyncFrame { endpoint: ep },
+            Request::GetReport {
+                ty: ReportType::Input,
+                id: 1,
+                interface: 0,
+                length: 8,
+            },
+            Request::SetReport {
+                ty: ReportType::Output,
+                id: 0,
+                interface: 0,
+                length: 1,
+            },
+            Request::GetIdle {
+                id: 0,
+                interface: 0,
+            },
+            Request::SetIdle {
+                duration: 0,
+                id: 2,
+                interface: 1,
+            },
+            Request::GetProtocol { interface: 0 },
+            Request::SetProtocol {
+                protocol: Protocol::Report,
+                interface: 0,
+            },
+            Request::Vendor(RawRequest::from_bytes([0xc0, 0x51, 1, 0, 2, 0, 0x40, 0])),
+        ]
+        .into_iter()
+        .for_each(round_trip);
+    }
+
+    #[test]
+    fn decode_malformed() {
+        let f = |b| Request::try_from(RawRequest::from_bytes(b));
+        // GET_DESCRIPTOR with direction OUT
+        assert!(matches!(
+            f([0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00]),
+            Err(InvalidRequest::UnexpectedDirection)
+        ));
+        // SET_ADDRESS with an address above 127
+        assert!(matches!(
+            f([0x00, 0x05, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            Err(InvalidRequest::UnexpectedValue)
+        ));
+        // CLEAR_FEATURE(TEST_MODE)
+        assert!(matches!(
+            f([0x00, 0x01, 0x02, 0x00, 0x00, 0x01, 0x00, 0x00]),
+            Err(InvalidRequest::UnknownFeature)
+        ));
+        // ENDPOINT_HALT on endpoint 0x10
+        assert!(matches!(
+            f([0x02, 0x01, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00]),
+            Err(InvalidRequest::InvalidEndpoint)
+        ));
+        // GET_STATUS with a data stage of 4 bytes
+        assert!(matches!(
+            f([0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00]),
+            Err(InvalidRequest::UnexpectedLength)
+        ));
+        assert!(matches!(
+            f([0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+            Err(InvalidRequest::ReservedType)
+        ));
+        // GET_REPORT of the reserved report type 4
+        assert!(matches!(
+            f([0xa1, 0x01, 0x00, 0x04, 0x00, 0x00, 0x40, 0x00]),
+            Err(InvalidRequest::UnexpectedValue)
+        ));
+        // CDC GET_ENCAPSULATED_RESPONSE looks like GET_REPORT of the reserved report type 0
+        assert!(matches!(
+            f([0xa1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00]),
+            Err(InvalidRequest::UnexpectedValue)
+        ));
+        // CDC SEND_BREAK has no HID counterpart
+        assert!(matches!(
+            f([0x21, 0x23, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]),
+            Err(InvalidRequest::UnknownRequest)
+        ));
+        // UAC SET_CUR of the sampling frequency of endpoint 0x01
+        assert!(matches!(
+            f([0x22, 0x01, 0x00, 0x01, 0x01, 0x00, 0x03, 0x00]),
+            Err(InvalidRequest::UnknownRequest)
+        ));
+        // UAC SET_CUR of the mute control of feature unit 2 on interface 0
+        assert!(matches!(
+            f([0x21, 0x01, 0x00, 0x01, 0x00, 0x02, 0x01, 0x00]),
+            Err(InvalidRequest::UnknownRequest)
+        ));
+    }
+
+    #[test]
+    fn hid_requests() {
+        let r = RawRequest::from(Request::SetProtocol {
+            protocol: Protocol::Boot,
+            interface: 2,
+        });
+        assert_eq!(
+            r.to_bytes(),
+            [0x21, 0x0b, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]
+        );
+        let r = RawRequest::from(Request::SetIdle {
+            duration: 125,
+            id: 0,
+            interface: 0,
+        });
+        assert_eq!(
+            r.to_bytes(),
+            [0x21, 0x0a, 0x00, 0x7d, 0x00, 0x00, 0x00, 0x00]
+        );
+        let r = RawRequest::from(Request::GetReport {
+            ty: ReportType::Feature,
+            id: 3,
+            interface: 1,
+            length: 64,
+        });
+        assert_eq!(
+            r.to_bytes(),
+            [0xa1, 0x01, 0x03, 0x03, 0x01, 0x00, 0x40, 0x00]
+        );
+    }
+
+    #[test]
+    fn service_interval() {
+        use descriptor::EndpointNumber;
+        let endpoint = |direction, transfer, max_packet_size, interval| {
+            Endpoint::new(
+                EndpointAddress::new(EndpointNumber::N1, direction),
+                transfer,
+                max_packet_size,
+                interval,
+            )
+        };
+        let mps = |size| MaxPacketSize::new(size, 0);
+        let t = |speed: Speed, e| speed.service_interval(&e);
+        let int = |size, interval| {
+            endpoint(
+                Direction::In,
+                EndpointTransfer::Interrupt,
+                mps(size),
+                interval,
+            )
+        };
+        let periodic = |microframes| ServiceInterval::Periodic { microframes };
+
+        assert_eq!(t(Speed::Full, int(8, 10)).unwrap(), periodic(80));
+        assert_eq!(t(Speed::High, int(8, 4)).unwrap().micros(), Some(1000));
+        assert_eq!(t(Speed::Super, int(8, 16)).unwrap(), periodic(1 << 15));
+        assert!(matches!(
+            t(Speed::High, int(8, 17)),
+            Err(InvalidServiceInterval::Interval(17))
+        ));
+        assert!(matches!(
+            t(Speed::Full, int(8, 0)),
+            Err(InvalidServiceInterval::Interval(0))
+        ));
+        assert!(matches!(
+            t(Speed::Low, int(16, 10)),
+            Err(InvalidServiceInterval::MaxPacketSize(_))
+        ));
+
+        let isoch = |mps, interval| endpoint(Direction::In, EndpointTransfer::Isoch, mps, interval);
+        assert_eq!(t(Speed::Full, isoch(mps(1023), 2)).unwrap(), periodic(16));
+        assert!(t(Speed::High, isoch(MaxPacketSize::new(1024, 2), 1)).is_ok());
+        assert!(matches!(
+            t(Speed::Full, isoch(MaxPacketSize::new(1023, 1), 1)),
+            Err(InvalidServiceInterval::MaxPacketSize(_))
+        ));
+
+        let bulk = |direction| endpoint(direction, EndpointTransfer::Bulk, mps(512), 4);
+        assert_eq!(
+            t(Speed::High, bulk(Direction::Out)).unwrap(),
+            ServiceInterval::NakRate { microframes: 4 }
+        );
+        assert_eq!(
+            t(Speed::High, bulk(Direction::In)).unwrap(),
+            ServiceInterval::Aperiodic
+        );
+        assert!(matches!(
+            t(Speed::Full, bulk(Direction::Out)),
+            Err(InvalidServiceInterval::MaxPacketSize(_))
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+//! Declarative tables to match drivers with devices.
+//!
+//! ```
+//! use usb_request::{class::WellKnown, matching::DeviceId};
+//!
+//! static TABLE: &[DeviceId<u32>] = &[
+//!     DeviceId::new(1).vendor_product(0x1234, 0x5678),
+//!     DeviceId::new(2).interface_code(WellKnown::HidBootKeyboard.code()),
+//! ];
+//! ```
+
+use crate::{
+    class::ClassCode,
+    descriptor::{Device, Interface},
+};
+
+/// An entry in a match table.
+///
+/// Every field that is `Some` must match. An entry with no fields set matches every device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceId<T> {
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    /// Inclusive range of `bcdDevice`.
+    pub device_range: Option<(u16, u16)>,
+    pub device_class: Option<u8>,
+    pub device_subclass: Option<u8>,
+    pub device_protocol: Option<u8>,
+    pub interface_class: Option<u8>,
+    pub interface_subclass: Option<u8>,
+    pub interface_protocol: Option<u8>,
+    pub interface_number: Option<u8>,
+    /// Driver-specific data.
+    pub data: T,
+}
+
+impl<T> DeviceId<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            vendor: None,
+            product: None,
+            device_range: None,
+            device_class: None,
+            device_subclass: None,
+            device_protocol: None,
+            interface_class: None,
+            interface_subclass: None,
+            interface_protocol: None,
+            interface_number: None,
+            data,
+        }
+    }
+
+    pub const fn vendor(self, vendor: u16) -> Self {
+        let mut s = self;
+        s.vendor = Some(vendor);
+        s
+    }
+
+    pub const fn vendor_product(self, vendor: u16, product: u16) -> Self {
+        let mut s = self;
+        s.vendor = Some(vendor);
+        s.product = Some(product);
+        s
+    }
+
+    pub const fn device_range(self, low: u16, high: u16) -> Self {
+        let mut s = self;
+        s.device_range = Some((low, high));
+        s
+    }
+
+    pub const fn device_class(self, class: u8) -> Self {
+        let mut s = self;
+        s.device_class = Some(class);
+        s
+    }
+
+    pub const fn device_code(self, code: ClassCode) -> Self {
+        let mut s = self;
+        s.device_class = Some(code.class);
+        s.device_subclass = Some(code.subclass);
+        s.device_protocol = Some(code.protocol);
+        s
+    }
+
+    pub const fn interface_class(self, class: u8) -> Self {
+        let mut s = self;
+        s.interface_class = Some(class);
+        s
+    }
+
+    pub const fn interface_code(self, code: ClassCode) -> Self {
+        let mut s = self;
+        s.interface_class = Some(code.class);
+        s.interface_subclass = Some(code.subclass);
+        s.interface_protocol = Some(code.protocol);
+        s
+    }
+
+    pub const fn interface_number(self, number: u8) -> Self {
+        let mut s = self;
+        s.interface_number = Some(number);
+        s
+    }
+
+    /// Whether this entry needs an interface to match.
+    pub const fn has_interface(&self) -> bool {
+        self.interface_class.is_some()
+            || self.interface_subclass.is_some()
+            || self.interface_protocol.is_some()
+            || self.interface_number.is_some()
+    }
+
+    /// Whether the device-level fields match.
+    ///
+    /// As on Linux, interface class fields never match a vendor-specific device unless the entry
+    /// also matches the vendor.
+    pub fn matches_device(&self, device: &Device) -> bool {
+        eq(self.vendor, device.vendor)
+            && eq(self.product, device.product)
+            && self
+                .device_range
+                .is_none_or(|(l, h)| (l..=h).contains(&device.device))
+            && eq(self.device_class, device.class)
+            && eq(self.device_subclass, device.subclass)
+            && eq(self.device_protocol, device.protocol)
+            && !(device.class == 0xff
+                && self.vendor.is_none()
+                && (self.interface_class.is_some()
+                    || self.interface_subclass.is_some()
+                    || self.interface_protocol.is_some()))
+    }
+
+    /// Whether the interface-level fields match.
+    pub fn matches_interface(&self, interface: &Interface) -> bool {
+        eq(self.interface_class, interface.class)
+            && eq(self.interface_subclass, interface.subclass)
+            && eq(self.interface_protocol, interface.protocol)
+            && eq(self.interface_number, interface.number)
+    }
+}
+
+fn eq<V: PartialEq>(field: Option<V>, value: V) -> bool {
+    field.is_none_or(|f| f == value)
+}
+
+/// A successful match.
+#[derive(Debug)]
+pub struct Match<'a, T> {
+    pub id: &'a DeviceId<T>,
+    /// The interface that matched, if the entry has interface fields.
+    pub interface: Option<Interface>,
+}
+
+impl<'a, T> Match<'a, T> {
+    pub fn data(&self) -> &'a T {
+        &self.id.data
+    }
+}
+
+/// Find the first entry in `table` that matches the device or one of its interfaces.
+///
+/// `interfaces` is walked once per entry with interface fields.
+/// With a [`ConfigurationTree`](crate::descriptor::ConfigurationTree) this can be
+/// `tree.interfaces().map(|i| *i.interface())`.
+pub fn find<'a, T, I>(
+    table: &'a [DeviceId<T>],
+    device: &Device,
+    interfaces: I,
+) -> Option<Match<'a, T>>
+where
+    I: IntoIterator<Item = Interface> + Clone,
+{
+    table.iter().find_map(|id| {
+        if !id.matches_device(device) {
+            None
+        } else if !id.has_interface() {
+            Some(Match {
+                id,
+                interface: None,
+            })
+        } else {
+            let interface = interfaces
+                .clone()
+                .into_iter()
+                .find(|i| id.matches_interface(i))?;
+            Some(Match {
+                id,
+                interface: Some(interface),
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::class::WellKnown;
+
+    const fn interface(number: u8, class: u8, subclass: u8, protocol: u8) -> Interface {
+        Interface {
+            number,
+            alternate_setting: 0,
+            num_endpoints: 1,
+            class,
+            subclass,
+            protocol,
+            index: 0,
+        }
+    }
+
+    static TABLE: &[DeviceId<&str>] = &[
+        DeviceId::new("quirk")
+            .vendor_product(0x1234, 0x5678)
+            .device_range(0x0100, 0x01ff),
+        DeviceId::new("keyboard").interface_code(WellKnown::HidBootKeyboard.code()),
+        DeviceId::new("vendor").vendor(0x1234).interface_class(0xff),
+        DeviceId::new("second").interface_number(1),
+    ];
+
+    #[test]
+    fn find() {
+        let mut device = Device {
+            usb: 0x0200,
+            class: 0,
+            subclass: 0,
+            protocol: 0,
+            max_packet_size_0: 64,
+            vendor: 0x1234,
+            product: 0x5678,
+            device: 0x0150,
+            index_manufacturer: 0,
+            index_product: 0,
+            index_serial_number: 0,
+            num_configurations: 1,
+        };
+        let interfaces = [interface(0, 0xff, 0, 0), interface(1, 3, 1, 1)];
+        let m = super::find(TABLE, &device, interfaces).unwrap();
+        assert_eq!(*m.data(), "quirk");
+        assert_eq!(m.interface, None);
+
+        device.device = 0x0200;
+        let m = super::find(TABLE, &device, interfaces).unwrap();
+        assert_eq!(*m.data(), "keyboard");
+        assert_eq!(m.interface, Some(interfaces[1]));
+
+        let m = super::find(TABLE, &device, interfaces[..1].iter().copied()).unwrap();
+        assert_eq!(*m.data(), "vendor");
+        assert_eq!(m.interface, Some(interfaces[0]));
+
+        // Interface classes of vendor-specific devices only match with a vendor.
+        device.class = 0xff;
+        device.vendor = 0x4321;
+        let m = super::find(TABLE, &device, interfaces).unwrap();
+        assert_eq!(*m.data(), "second");
+        assert!(super::find(TABLE, &device, interfaces[..1].iter().copied()).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+//! An in-memory device for testing host-side code.
+
+use crate::{
+    descriptor::{ConfigurationTree, GetDescriptor},
+    transfer::{AsyncControlTransfer, ControlError, ControlTransfer},
+    Feature, RawRequest, Recipient, Request,
+};
+use core::{convert::Infallible, future::Future};
+
+/// A scripted deviation from the normal answer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    Stall,
+    /// Transfer at most this many bytes.
+    Short(usize),
+    Timeout,
+}
+
+/// A device that answers standard requests from descriptor bytes.
+///
+/// `script` is consulted before every request and may inject a [`Fault`].
+/// Requests the device does not understand are stalled, as are interface requests for interface
+/// numbers above 31. Requests to another address than the device's time out.
+pub struct MockDevice<'a, S> {
+    device: &'a [u8],
+    configurations: &'a [&'a [u8]],
+    strings: &'a [&'a [u8]],
+    script: S,
+    address: u8,
+    configuration: u8,
+    alternate_settings: [u8; 32],
+    halted: u32,
+}
+
+impl<'a, S> MockDevice<'a, S>
+where
+    S: FnMut(&RawRequest) -> Option<Fault>,
+{
+    /// `strings[0]` is the language table, the other entries are string descriptors.
+    pub fn new(
+        device: &'a [u8],
+        configurations: &'a [&'a [u8]],
+        strings: &'a [&'a [u8]],
+        script: S,
+    ) -> Self {
+        Self {
+            device,
+            configurations,
+            strings,
+            script,
+            address: 0,
+            configuration: 0,
+            alternate_settings: [0; 32],
+            halted: 0,
+        }
+    }
+
+    /// The address assigned with SET_ADDRESS.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// The value of the active configuration, 0 if unconfigured.
+    pub fn configuration(&self) -> u8 {
+        self.configuration
+    }
+
+    fn tree(&self) -> Option<ConfigurationTree<'a>> {
+        self.configurations
+            .iter()
+            .filter_map(|c| ConfigurationTree::new(c).ok())
+            .find(|t| t.configuration().configuration_value == self.configuration)
+    }
+
+    fn answer(&mut self, request: RawRequest, data: &mut [u8]) -> Option<usize> {
+        let mut reply = |src: &[u8]| {
+            let n = src.len().min(request.length.into());
+            data[..n].copy_from_slice(&src[..n]);
+            Some(n)
+        };
+        match Request::try_from(request).ok()? {
+            Request::GetStatus { recipient } => match recipient {
+                Recipient::Device | Recipient::Interface(_) => reply(&[0; 2]),
+                Recipient::Endpoint(e) => {
+                    let halted = self.halted & e.bit() != 0;
+                    reply(&[halted.into(), 0])
+                }
+            },
+            Request::SetFeature {
+                feature: Feature::EndpointHalt(e),
+            } => {
+                self.halted |= e.bit();
+                Some(0)
+            }
+            Request::ClearFeature {
+                feature: Feature::EndpointHalt(e),
+            } => {
+                self.halted &= !e.bit();
+                Some(0)
+            }
+            Request::SetAddress { address } => {
+                self.address = address;
+                Some(0)
+            }
+            Request::GetDescriptor { ty } => match ty {
+                GetDescriptor::Device => reply(self.device),
+                GetDescriptor::Configuration { index, .. } => {
+                    reply(self.configurations.get(usize::from(index))?)
+                }
+                GetDescriptor::Languages => reply(self.strings.first()?),
+                GetDescriptor::String { index, .. } => reply(self.strings.get(usize::from(index))?),
+                _ => None,
+            },
+            Request::GetConfiguration => reply(&[self.configuration]),
+            Request::SetConfiguration { value } => {
+                self.configuration = value;
+                if value != 0 && self.tree().is_none() {
+                    self.configuration = 0;
+                    return None;
+                }
+                self.alternate_settings = [0; 32];
+                self.halted = 0;
+                Some(0)
+            }
+            Request::GetInterface { interface } => {
+                self.tree()?.interface(interface, 0)?;
+                reply(&[*self.alternate_settings.get(usize::from(interface))?])
+            }
+            Request::SetInterface {
+                interface,
+                alternate_setting,
+            } => {
+                self.tree()?.interface(interface, alternate_setting)?;
+                *self.alternate_settings.get_mut(usize::from(interface))? = alternate_setting;
+                Some(0)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<S> ControlTransfer for MockDevice<'_, S>
+where
+    S: FnMut(&RawRequest) -> Option<Fault>,
+{
+    type Error = Infallible;
+
+    fn control(
+        &mut self,
+        address: u8,
+        _: u16,
+        request: RawRequest,
+        data: &mut [u8],
+    ) -> Result<usize, ControlError<Self::Error>> {
+        if address != self.address {
+            return Err(ControlError::Timeout);
+        }
+        let max = match (self.script)(&request) {
+            Some(Fault::Stall) => return Err(ControlError::Stall),
+            Some(Fault::Timeout) => return Err(ControlError::Timeout),
+            Some(Fault::Short(n)) => n,
+            None => usize::MAX,
+        };
+        self.answer(request, data)
+            .map(|n| n.min(max))
+            .ok_or(ControlError::Stall)
+    }
+}
+
+impl<S> AsyncControlTransfer for MockDevice<'_, S>
+where
+    S: FnMut(&RawRequest) -> Option<Fault>,
+{
+    type Error = Infallible;
+
+    fn control(
+        &mut self,
+        address: u8,
+        max_packet_size_0: u16,
+        request: RawRequest,
+        data: &mut [u8],
+    ) -> impl Future<Output = Result<usize, ControlError<Self::Error>>> {
+        core::future::ready(ControlTransfer::control(
+            self,
+            address,
+            max_packet_size_0,
+            request,
+            data,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::descriptor::{Direction, EndpointAddress, EndpointNumber};
+
+    #[test]
+    fn standard_requests() {
+        const CONFIGURATION: &[u8] = &[
+            0x09, 0x02, 0x19, 0x00, 0x01, 0x02, 0x00, 0x80, 0x32, // configuration
+            0x09, 0x04, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x00, // interface
+            0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+        ];
+        let mut d = MockDevice::new(&[], &[CONFIGURATION], &[], |_: &RawRequest| None);
+        let r = RawRequest::from(Request::GetConfiguration);
+        assert_eq!(
+            ControlTransfer::control(&mut d, 5, 8, r, &mut [0]),
+            Err(ControlError::Timeout)
+        );
+        let mut t = |r: Request| {
+            let mut buf = [0; 2];
+            ControlTransfer::control(&mut d, 0, 8, r.into(), &mut buf).map(|n| (n, buf))
+        };
+        let ep = EndpointAddress::new(EndpointNumber::N1, Direction::In);
+        let halt = Feature::EndpointHalt(ep);
+        let status = Request::GetStatus {
+            recipient: Recipient::Endpoint(ep),
+        };
+        assert_eq!(
+            t(Request::SetConfiguration { value: 1 }),
+            Err(ControlError::Stall)
+        );
+        assert_eq!(t(Request::SetConfiguration { value: 2 }), Ok((0, [0; 2])));
+        assert_eq!(t(Request::GetConfiguration), Ok((1, [2, 0])));
+        assert_eq!(t(Request::SetFeature { feature: halt }), Ok((0, [0; 2])));
+        assert_eq!(t(status), Ok((2, [1, 0])));
+        assert_eq!(t(Request::ClearFeature { feature: halt }), Ok((0, [0; 2])));
+        assert_eq!(t(status), Ok((2, [0, 0])));
+        let set_interface = |alternate_setting| Request::SetInterface {
+            interface: 0,
+            alternate_setting,
+        };
+        assert_eq!(t(set_interface(1)), Err(ControlError::Stall));
+        assert_eq!(t(set_interface(0)), Ok((0, [0; 2])));
+    }
+}
@@ -0,0 +1,63 @@
+//! Responses to GET_STATUS.
+
+use core::fmt;
+
+macro_rules! status {
+    ($(#[$m:meta])* $s:ident { $($i:literal $f:ident $n:literal)* }) => {
+        $(#[$m])*
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        pub struct $s(u16);
+
+        impl $s {
+            $(
+                pub fn $f(&self) -> bool {
+                    self.0 & 1 << $i != 0
+                }
+            )*
+
+            pub fn from_bytes(b: [u8; 2]) -> Self {
+                Self(u16::from_le_bytes(b))
+            }
+
+            pub fn to_bytes(&self) -> [u8; 2] {
+                self.0.to_le_bytes()
+            }
+        }
+
+        impl fmt::Debug for $s {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut f = f.debug_set();
+                $(self.$f().then(|| f.entry(&format_args!($n)));)*
+                f.finish()
+            }
+        }
+    };
+}
+
+status!(
+    /// Status of a device.
+    DeviceStatus {
+        0 self_powered "SELF_POWERED"
+        1 remote_wakeup "REMOTE_WAKEUP"
+        2 u1_enable "U1_ENABLE"
+        3 u2_enable "U2_ENABLE"
+        4 ltm_enable "LTM_ENABLE"
+    }
+);
+
+status!(
+    /// Status of an interface.
+    ///
+    /// Only SuperSpeed devices report a non-zero interface status.
+    InterfaceStatus {
+        0 remote_wakeup_capable "REMOTE_WAKEUP_CAPABLE"
+        1 remote_wakeup "REMOTE_WAKEUP"
+    }
+);
+
+status!(
+    /// Status of an endpoint.
+    EndpointStatus {
+        0 halt "HALT"
+    }
+);
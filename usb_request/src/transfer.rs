@@ -0,0 +1,51 @@
+//! Abstractions for issuing control requests.
+
+use crate::RawRequest;
+use core::future::Future;
+
+/// The default control pipes of the devices on a bus.
+///
+/// Each request names the device `address` and the max packet size of its endpoint 0, which the
+/// implementation must program into the pipe before the transfer. The max packet size is 8 until
+/// the host has read it from the device descriptor. After a successful SET_ADDRESS the
+/// implementation must wait for the 2 ms recovery interval before the next request.
+pub trait ControlTransfer {
+    type Error;
+
+    /// Perform `request` with `data` as the data stage.
+    ///
+    /// `data` holds at least `request.length` bytes. Returns the amount of bytes transferred.
+    fn control(
+        &mut self,
+        address: u8,
+        max_packet_size_0: u16,
+        request: RawRequest,
+        data: &mut [u8],
+    ) -> Result<usize, ControlError<Self::Error>>;
+}
+
+/// Like [`ControlTransfer`], but asynchronous.
+pub trait AsyncControlTransfer {
+    type Error;
+
+    /// Perform `request` with `data` as the data stage.
+    ///
+    /// `data` holds at least `request.length` bytes. Returns the amount of bytes transferred.
+    fn control(
+        &mut self,
+        address: u8,
+        max_packet_size_0: u16,
+        request: RawRequest,
+        data: &mut [u8],
+    ) -> impl Future<Output = Result<usize, ControlError<Self::Error>>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlError<E> {
+    /// The device answered with a STALL handshake.
+    Stall,
+    /// The device did not answer in time.
+    Timeout,
+    /// A backend-specific error.
+    Other(E),
+}
@@ -0,0 +1,377 @@
+//! Device and endpoint contexts of the eXtensible Host Controller Interface.
+//!
+//! The layouts follow section 6.2 of the xHCI specification, revision 1.2.
+
+use crate::{
+    descriptor::{
+        Direction, Endpoint, EndpointAddress, EndpointTransfer, SuperSpeedCompanion,
+        SuperSpeedPlusIsochCompanion,
+    },
+    le_bytes, InvalidServiceInterval, ServiceInterval, Speed,
+};
+
+/// The Device Context Index of an endpoint.
+///
+/// Both directions of endpoint 0 share index 1.
+pub const fn context_index(address: EndpointAddress) -> u8 {
+    match (address.number() as u8, address.direction()) {
+        (0, _) => 1,
+        (n, Direction::Out) => n * 2,
+        (n, Direction::In) => n * 2 + 1,
+    }
+}
+
+/// The xHCI Protocol Speed ID of the default speed mappings.
+const fn speed_id(speed: Speed) -> u32 {
+    match speed {
+        Speed::Full => 1,
+        Speed::Low => 2,
+        Speed::High => 3,
+        Speed::Super => 4,
+        Speed::SuperPlus => 5,
+    }
+}
+
+/// Route a device through hubs, with the ports to take below the root hub.
+///
+/// Returns `None` for more than 5 hubs or ports above 15.
+pub fn route_string(ports: &[u8]) -> Option<u32> {
+    if ports.len() > 5 {
+        return None;
+    }
+    ports.iter().enumerate().try_fold(0, |r, (tier, &port)| {
+        (1..=15)
+            .contains(&port)
+            .then_some(r | u32::from(port) << (tier * 4))
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotContext {
+    /// Ports of the hubs between the root hub and the device, see [`route_string`].
+    pub route_string: u32,
+    pub speed: Speed,
+    /// Whether this is a hub with a transaction translator per port.
+    pub multi_tt: bool,
+    pub hub: bool,
+    /// The highest [`context_index`] of the endpoints in use.
+    pub context_entries: u8,
+    pub max_exit_latency: u16,
+    /// The root hub port, starting at 1.
+    pub root_hub_port: u8,
+    /// The amount of downstream ports if this is a hub.
+    pub num_ports: u8,
+    /// The slot of the high-speed hub a low or full-speed device is attached to.
+    pub parent_hub_slot: u8,
+    /// The port of the parent high-speed hub.
+    pub parent_port: u8,
+    /// The TT think time of a high-speed hub, in units of 8 full-speed bit times minus 1.
+    pub tt_think_time: u8,
+    pub interrupter_target: u16,
+}
+
+impl SlotContext {
+    /// The slot of a device with only endpoint 0.
+    pub const fn new(speed: Speed, root_hub_port: u8, route_string: u32) -> Self {
+        Self {
+            route_string,
+            speed,
+            multi_tt: false,
+            hub: false,
+            context_entries: 1,
+            max_exit_latency: 0,
+            root_hub_port,
+            num_ports: 0,
+            parent_hub_slot: 0,
+            parent_port: 0,
+            tt_think_time: 0,
+            interrupter_target: 0,
+        }
+    }
+
+    /// Raise the context entries to include `endpoint`.
+    pub const fn add_endpoint(&mut self, endpoint: EndpointAddress) {
+        let i = context_index(endpoint);
+        if i > self.context_entries {
+            self.context_entries = i;
+        }
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        le_bytes([
+            (self.route_string & 0xfffff)
+                | speed_id(self.speed) << 20
+                | (self.multi_tt as u32) << 25
+                | (self.hub as u32) << 26
+                | (self.context_entries as u32) << 27,
+            self.max_exit_latency as u32
+                | (self.root_hub_port as u32) << 16
+                | (self.num_ports as u32) << 24,
+            self.parent_hub_slot as u32
+                | (self.parent_port as u32) << 8
+                | ((self.tt_think_time & 0x3) as u32) << 16
+                | ((self.interrupter_target & 0x3ff) as u32) << 22,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointType {
+    IsochOut = 1,
+    BulkOut = 2,
+    InterruptOut = 3,
+    Control = 4,
+    IsochIn = 5,
+    BulkIn = 6,
+    InterruptIn = 7,
+}
+
+impl EndpointType {
+    pub const fn new(transfer: EndpointTransfer, direction: Direction) -> Self {
+        match (transfer, direction) {
+            (EndpointTransfer::Control, _) => Self::Control,
+            (EndpointTransfer::Isoch, Direction::Out) => Self::IsochOut,
+            (EndpointTransfer::Bulk, Direction::Out) => Self::BulkOut,
+            (EndpointTransfer::Interrupt, Direction::Out) => Self::InterruptOut,
+            (EndpointTransfer::Isoch, Direction::In) => Self::IsochIn,
+            (EndpointTransfer::Bulk, Direction::In) => Self::BulkIn,
+            (EndpointTransfer::Interrupt, Direction::In) => Self::InterruptIn,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EndpointContext {
+    pub ty: EndpointType,
+    /// Bursts per service interval of a SuperSpeed isochronous endpoint, minus 1.
+    pub mult: u8,
+    /// The base 2 logarithm of the amount of primary streams, minus 1. 0 without streams.
+    pub max_primary_streams: u8,
+    /// The service interval as a power of 2 in units of 125 µs.
+    pub interval: u8,
+    /// The amount of retries on errors, 0 for unlimited.
+    pub error_count: u8,
+    /// Additional packets per burst.
+    pub max_burst: u8,
+    pub max_packet_size: u16,
+    /// The address of the first TRB of the transfer ring, with the dequeue cycle state in bit 0.
+    pub dequeue_pointer: u64,
+    pub average_trb_length: u16,
+    /// Bytes transferred per service interval of a periodic endpoint.
+    pub max_esit_payload: u32,
+}
+
+impl EndpointContext {
+    /// The context of endpoint 0 with the max packet size from the device descriptor.
+    ///
+    /// For SuperSpeed the max packet size is 512.
+    pub const fn control(max_packet_size_0: u16) -> Self {
+        Self {
+            ty: EndpointType::Control,
+            mult: 0,
+            max_primary_streams: 0,
+            interval: 0,
+            error_count: 3,
+            max_burst: 0,
+            max_packet_size: max_packet_size_0,
+            dequeue_pointer: 0,
+            average_trb_length: 8,
+            max_esit_payload: 0,
+        }
+    }
+
+    /// Derive the context from an endpoint at `speed`, with the companion of SuperSpeed endpoints.
+    ///
+    /// The SuperSpeedPlus isochronous companion is required if the SuperSpeed companion announces
+    /// one, as it holds the bytes per service interval.
+    /// The average TRB length is set to the value recommended in section 4.14.1.1.
+    pub const fn new(
+        speed: Speed,
+        endpoint: &Endpoint,
+        companion: Option<&SuperSpeedCompanion>,
+        isoch_companion: Option<&SuperSpeedPlusIsochCompanion>,
+    ) -> Result<Self, InvalidEndpointContext> {
+        let interval = match speed.service_interval(endpoint) {
+            Ok(ServiceInterval::Periodic { microframes }) => microframes.ilog2() as u8,
+            Ok(ServiceInterval::NakRate { microframes: 0 }) | Ok(ServiceInterval::Aperiodic) => 0,
+            Ok(ServiceInterval::NakRate { microframes }) => microframes.ilog2() as u8,
+            Err(e) => return Err(InvalidEndpointContext::ServiceInterval(e)),
+        };
+        let transfer = endpoint.attributes.transfer();
+        let mps = endpoint.max_packet_size;
+        let periodic = matches!(
+            transfer,
+            EndpointTransfer::Interrupt | EndpointTransfer::Isoch
+        );
+        let (max_burst, mult, esit) = match companion {
+            Some(c) => match (transfer, c.ssp_isoch_companion(), isoch_companion) {
+                (EndpointTransfer::Isoch, true, Some(i)) => (c.max_burst, 0, i.bytes_per_interval),
+                (EndpointTransfer::Isoch, true, None) => {
+                    return Err(InvalidEndpointContext::MissingIsochCompanion)
+                }
+                (EndpointTransfer::Isoch, false, _) => {
+                    (c.max_burst, c.mult(), c.bytes_per_interval as u32)
+                }
+                _ => (c.max_burst, 0, c.bytes_per_interval as u32),
+            },
+            None if periodic => (mps.additional_transactions(), 0, mps.bytes_per_interval()),
+            None => (0, 0, 0),
+        };
+        let (error_count, average_trb_length) = match transfer {
+            EndpointTransfer::Control => (3, 8),
+            EndpointTransfer::Interrupt => (3, 1024),
+            EndpointTransfer::Bulk => (3, 3072),
+            EndpointTransfer::Isoch => (0, 3072),
+        };
+        Ok(Self {
+            ty: EndpointType::new(transfer, endpoint.address.direction()),
+            mult,
+            max_primary_streams: 0,
+            interval,
+            error_count,
+            max_burst,
+            max_packet_size: mps.size(),
+            dequeue_pointer: 0,
+            average_trb_length,
+            max_esit_payload: if periodic { esit } else { 0 },
+        })
+    }
+
+    pub const fn to_bytes(&self) -> [u8; 32] {
+        let [d0, d1] = [
+            self.dequeue_pointer as u32,
+            (self.dequeue_pointer >> 32) as u32,
+        ];
+        le_bytes([
+            ((self.mult & 0x3) as u32) << 8
+                | ((self.max_primary_streams & 0x1f) as u32) << 10
+                | (self.interval as u32) << 16
+                | (self.max_esit_payload >> 16 & 0xff) << 24,
+            ((self.error_count & 0x3) as u32) << 1
+                | (self.ty as u32) << 3
+                | (self.max_burst as u32) << 8
+                | (self.max_packet_size as u32) << 16,
+            d0,
+            d1,
+            self.average_trb_length as u32 | (self.max_esit_payload & 0xffff) << 16,
+            0,
+            0,
+            0,
+        ])
+    }
+}
+
+#[derive(Debug)]
+pub enum InvalidEndpointContext {
+    ServiceInterval(InvalidServiceInterval),
+    /// The SuperSpeedPlus isochronous endpoint companion was not given.
+    MissingIsochCompanion,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::descriptor::{EndpointNumber, MaxPacketSize};
+
+    const ADDRESS: EndpointAddress = EndpointAddress::new(EndpointNumber::N1, Direction::In);
+
+    #[test]
+    fn endpoint_context() {
+        let e = Endpoint::new(
+            ADDRESS,
+            EndpointTransfer::Interrupt,
+            MaxPacketSize::new(64, 1),
+            4,
+        );
+        let c = EndpointContext::new(Speed::High, &e, None, None).unwrap();
+        assert_eq!(c.ty, EndpointType::InterruptIn);
+        assert_eq!((c.interval, c.max_burst, c.max_esit_payload), (3, 1, 128));
+        assert_eq!(
+            c.to_bytes()[..20],
+            [0, 0, 3, 0, 0x3e, 1, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 128, 0]
+        );
+
+        let e = Endpoint::new(
+            ADDRESS,
+            EndpointTransfer::Interrupt,
+            MaxPacketSize::new(8, 0),
+            10,
+        );
+        let c = EndpointContext::new(Speed::Full, &e, None, None).unwrap();
+        assert_eq!(c.interval, 6);
+        let e = Endpoint::new(
+            ADDRESS,
+            EndpointTransfer::Isoch,
+            MaxPacketSize::new(1023, 0),
+            1,
+        );
+        let c = EndpointContext::new(Speed::Full, &e, None, None).unwrap();
+        assert_eq!(
+            (c.interval, c.error_count, c.ty),
+            (3, 0, EndpointType::IsochIn)
+        );
+        assert!(EndpointContext::new(Speed::High, &e, None, None).is_ok());
+        assert!(EndpointContext::new(Speed::Low, &e, None, None).is_err());
+
+        let e = Endpoint::new(
+            ADDRESS,
+            EndpointTransfer::Isoch,
+            MaxPacketSize::new(1024, 0),
+            1,
+        );
+        let companion = SuperSpeedCompanion {
+            max_burst: 15,
+            attributes: 2,
+            bytes_per_interval: 0xc000,
+        };
+        let c = EndpointContext::new(Speed::Super, &e, Some(&companion), None).unwrap();
+        assert_eq!((c.mult, c.max_burst, c.max_esit_payload), (2, 15, 0xc000));
+        let companion = SuperSpeedCompanion {
+            max_burst: 15,
+            attributes: 1 << 7,
+            bytes_per_interval: 1,
+        };
+        let isoch = SuperSpeedPlusIsochCompanion {
+            bytes_per_interval: 0x6_0000,
+        };
+        let c = EndpointContext::new(Speed::SuperPlus, &e, Some(&companion), Some(&isoch)).unwrap();
+        assert_eq!((c.mult, c.max_burst, c.max_esit_payload), (0, 15, 0x6_0000));
+        assert_eq!(c.to_bytes()[3], 0x06);
+        assert!(matches!(
+            EndpointContext::new(Speed::SuperPlus, &e, Some(&companion), None),
+            Err(InvalidEndpointContext::MissingIsochCompanion)
+        ));
+        let e = Endpoint::new(
+            ADDRESS,
+            EndpointTransfer::Bulk,
+            MaxPacketSize::new(1024, 0),
+            0,
+        );
+        let c = EndpointContext::new(Speed::Super, &e, Some(&companion), None).unwrap();
+        assert_eq!(
+            (c.mult, c.max_esit_payload, c.average_trb_length),
+            (0, 0, 3072)
+        );
+    }
+
+    #[test]
+    fn slot_context() {
+        assert_eq!(route_string(&[3, 12]), Some(0xc3));
+        assert_eq!(route_string(&[0]), None);
+        assert_eq!(route_string(&[1; 6]), None);
+        let mut s = SlotContext::new(Speed::High, 2, 0xc3);
+        s.add_endpoint(EndpointAddress::new(EndpointNumber::N2, Direction::In));
+        s.add_endpoint(EndpointAddress::new(EndpointNumber::N1, Direction::Out));
+        assert_eq!(s.context_entries, 5);
+        let mut b = [0; 32];
+        b[..7].copy_from_slice(&[0xc3, 0, 0x30, 5 << 3, 0, 0, 2]);
+        assert_eq!(s.to_bytes(), b);
+        let ep0 = EndpointAddress::new(EndpointNumber::N0, Direction::In);
+        assert_eq!(context_index(ep0), 1);
+    }
+}